@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use orgize::ast::Headline;
+use orgize::rowan::ast::AstNode;
+use orgize::{Org, TextRange};
+use slugify::slugify;
+
+use crate::page;
+
+/// Section anchors of a page.
+///
+/// A `CUSTOM_ID`, or else `ID`, property wins over the title slug. Slugs
+/// colliding with an earlier anchor get a `-2`, `-3`... suffix, in document
+/// order, and so do repeated explicit ids. `.`-prefixed layout headlines don't
+/// get one.
+#[derive(Debug, Default)]
+pub struct Anchors {
+    /// Anchor of each headline, by its position on the document
+    by_headline: HashMap<TextRange, String>,
+    /// `#custom-id` link targets
+    by_custom_id: HashMap<String, (String, String)>,
+    /// `*Heading` link targets, the first headline with that title
+    by_title: HashMap<String, (String, String)>,
+}
+
+impl Anchors {
+    pub fn new(doc: &Org) -> Self {
+        let headlines: Vec<Headline> = doc
+            .document()
+            .syntax()
+            .descendants()
+            .filter_map(Headline::cast)
+            .filter(|h| !page::headline_title(h).starts_with('.'))
+            .collect();
+
+        let explicit_id = |headline: &Headline| {
+            let properties = headline.properties()?;
+            properties
+                .get("CUSTOM_ID")
+                .or_else(|| properties.get("ID"))
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+        };
+
+        // Explicit ids are reserved first so a slug can't take them
+        let mut used: HashSet<String> = headlines.iter().filter_map(explicit_id).collect();
+        // Explicit ids given to a headline already, later ones get a suffix
+        let mut taken: HashSet<String> = HashSet::new();
+        let mut anchors = Self::default();
+        for headline in &headlines {
+            let title = page::headline_title(headline);
+            let anchor = match explicit_id(headline) {
+                Some(id) if taken.insert(id.clone()) => id,
+                Some(id) => {
+                    let anchor = unique(&id, &mut used);
+                    log::warn!("Duplicate anchor '{id}' of '{title}', using '{anchor}'");
+                    anchor
+                }
+                None => unique(&slugify!(&title), &mut used),
+            };
+
+            if let Some(custom_id) = headline.properties().and_then(|p| p.get("CUSTOM_ID")) {
+                anchors
+                    .by_custom_id
+                    .entry(custom_id.trim().to_string())
+                    .or_insert_with(|| (anchor.clone(), title.clone()));
+            }
+            anchors
+                .by_title
+                .entry(title.trim().to_string())
+                .or_insert_with(|| (anchor.clone(), title));
+            anchors
+                .by_headline
+                .insert(headline.syntax().text_range(), anchor);
+        }
+        anchors
+    }
+
+    /// Anchor of a headline section
+    pub fn get(&self, headline: &Headline) -> Option<&str> {
+        self.by_headline
+            .get(&headline.syntax().text_range())
+            .map(|a| a.as_str())
+    }
+
+    /// Resolves a `#custom-id` or `*Heading` link into the anchor and title of
+    /// its section. `None` for other links or missing sections.
+    pub fn resolve(&self, link: &str) -> Option<(&str, &str)> {
+        let target = if let Some(custom_id) = link.strip_prefix('#') {
+            self.by_custom_id.get(custom_id)
+        } else if let Some(title) = link.strip_prefix('*') {
+            self.by_title.get(title.trim())
+        } else {
+            None
+        };
+        target.map(|(anchor, title)| (anchor.as_str(), title.as_str()))
+    }
+}
+
+/// `base`, or else the first of `base-2`, `base-3`... not `used` yet
+fn unique(base: &str, used: &mut HashSet<String>) -> String {
+    let mut anchor = base.to_string();
+    let mut n = 2;
+    while used.contains(&anchor) {
+        anchor = format!("{base}-{n}");
+        n += 1;
+    }
+    used.insert(anchor.clone());
+    anchor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use indoc::indoc;
+
+    #[gtest]
+    fn unique_and_custom() {
+        let doc = Org::parse(indoc! {r###"
+          * Recursive headings
+          ** Recursive headings
+          * .wide
+          ** Recursive headings
+          * Stable
+          :PROPERTIES:
+          :CUSTOM_ID: recursive-headings-2
+          :END:
+          * With ID
+          :PROPERTIES:
+          :ID: 6f2b7c1e-1d1c-4f5e-9e0b-2a9e6f1c3d4a
+          :END:
+          * Copied
+          :PROPERTIES:
+          :CUSTOM_ID: recursive-headings-2
+          :END:
+        "###});
+        let anchors = Anchors::new(&doc);
+
+        let all: Vec<&str> = doc
+            .document()
+            .syntax()
+            .descendants()
+            .filter_map(Headline::cast)
+            .filter_map(|h| anchors.get(&h))
+            .collect();
+        expect_that!(
+            all,
+            eq(&vec![
+                "recursive-headings",
+                "recursive-headings-3",
+                "recursive-headings-4",
+                "recursive-headings-2",
+                "6f2b7c1e-1d1c-4f5e-9e0b-2a9e6f1c3d4a",
+                "recursive-headings-2-2",
+            ])
+        );
+
+        expect_that!(
+            anchors.resolve("#recursive-headings-2"),
+            some(eq(("recursive-headings-2", "Stable")))
+        );
+        expect_that!(
+            anchors.resolve("*Recursive headings"),
+            some(eq(("recursive-headings", "Recursive headings")))
+        );
+        expect_that!(anchors.resolve("*Missing"), none());
+        expect_that!(anchors.resolve("https://example.com"), none());
+    }
+}
@@ -0,0 +1,320 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use orgize::Org;
+use orgize::ast::{Keyword, PropertyDrawer};
+use orgize::export::HtmlEscape;
+use orgize::rowan::ast::AstNode;
+
+use crate::page;
+use crate::site;
+
+/// Keyword and property requesting a generated listing.
+///
+/// - `#+generate_index: OPTIONS` renders the listing where the keyword is.
+/// - `:generate_index: OPTIONS` renders it where the property drawer is.
+pub const GENERATE_INDEX: &str = "generate_index";
+
+/// Pages to list on an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scope {
+    /// Pages under the directory named after the page, `b.org` lists `b/**/*.org`
+    #[default]
+    Descendants,
+    /// Other pages on the same directory
+    Siblings,
+}
+
+/// Options on a `generate_index` keyword or property, like `:scope siblings :per_page 20`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Options {
+    pub scope: Scope,
+    /// Entries per page. Everything goes on a single page when missing.
+    pub per_page: Option<usize>,
+}
+
+impl Options {
+    pub fn parse(value: &str, file_rel_path: &Path) -> Self {
+        let mut options = Options::default();
+
+        let mut words = value.split_whitespace();
+        while let Some(word) = words.next() {
+            match (word, words.clone().next()) {
+                (":scope", Some("descendants")) => options.scope = Scope::Descendants,
+                (":scope", Some("siblings")) => options.scope = Scope::Siblings,
+                (":per_page", Some(n)) if n.parse::<usize>().is_ok_and(|n| n > 0) => {
+                    options.per_page = n.parse().ok();
+                }
+                (":scope" | ":per_page", value) => {
+                    log::warn!(
+                        "{}: Ignoring invalid {GENERATE_INDEX} option {word} {}",
+                        file_rel_path.display(),
+                        value.unwrap_or_default()
+                    );
+                }
+                _ => continue,
+            }
+            words.next();
+        }
+
+        options
+    }
+
+    /// Finds the archive requested by a document, if any.
+    pub fn find(doc: &Org, file_rel_path: &Path) -> Option<Self> {
+        let root = doc.document().syntax().clone();
+
+        root.descendants()
+            .find_map(|node| {
+                if let Some(keyword) = Keyword::cast(node.clone())
+                    && keyword.key().eq_ignore_ascii_case(GENERATE_INDEX)
+                {
+                    return Some(keyword.value().to_string());
+                }
+                PropertyDrawer::cast(node)?
+                    .get(GENERATE_INDEX)
+                    .map(|v| v.to_string())
+            })
+            .map(|value| Self::parse(&value, file_rel_path))
+    }
+}
+
+/// A generated listing of pages, grouped by year and month.
+pub struct Archive<'a> {
+    /// Page holding the archive
+    rel_path: &'a Path,
+    options: Options,
+    /// Listed pages, newest first
+    pages: Vec<&'a site::Page>,
+}
+
+impl<'a> Archive<'a> {
+    pub fn new(site: &'a site::Site, rel_path: &'a Path, options: Options) -> Self {
+        let dir = rel_path.parent().unwrap_or(Path::new(""));
+        let children = rel_path.with_extension("");
+
+        let mut pages: Vec<&site::Page> = site
+            .pages()
+            .filter(|p| p.rel_path != rel_path)
+            .filter(|p| match options.scope {
+                Scope::Descendants => p.rel_path.starts_with(&children),
+                Scope::Siblings => p.rel_path.parent() == Some(dir),
+            })
+            .collect();
+        pages.sort_by_key(|p| std::cmp::Reverse(p.date));
+
+        Self {
+            rel_path,
+            options,
+            pages,
+        }
+    }
+
+    /// Number of pages needed to list every entry
+    pub fn page_count(&self) -> usize {
+        match self.options.per_page {
+            Some(per_page) => self.pages.len().div_ceil(per_page).max(1),
+            None => 1,
+        }
+    }
+
+    /// Site path of the n-th page of the archive, starting at 1.
+    ///
+    /// The first one is the page holding the archive, the rest go under `page/N`.
+    pub fn page_path(&self, n: usize) -> String {
+        let mut path = self.rel_path.to_path_buf();
+        path.set_extension("");
+        if n <= 1 {
+            format!("/{}", path.display())
+        } else {
+            format!("/{}/page/{n}", path.display())
+        }
+    }
+
+    /// Renders the n-th page of the archive, starting at 1.
+    ///
+    /// Years get a `depth` heading and months a `depth + 1` one.
+    pub fn to_html(&self, n: usize, depth: i8) -> String {
+        let per_page = self.options.per_page.unwrap_or(self.pages.len().max(1));
+        let year_heading = page::heading(depth);
+        let month_heading = page::heading(depth + 1);
+
+        let mut html = String::from(r#"<div class="archive">"#);
+        // (Year, Month) of the open <ul>
+        let mut group: Option<(Option<String>, Option<String>)> = None;
+        for p in self.pages.iter().skip((n - 1) * per_page).take(per_page) {
+            let p_group = (
+                p.date.map(|d| d.format("%Y").to_string()),
+                p.date.map(|d| d.format("%B").to_string()),
+            );
+
+            if group.as_ref() != Some(&p_group) {
+                if group.is_some() {
+                    html.push_str("</ul>");
+                }
+                if group.as_ref().map(|g| &g.0) != Some(&p_group.0) {
+                    let _ = write!(
+                        html,
+                        "<{year_heading}>{}</{year_heading}>",
+                        p_group.0.as_deref().unwrap_or("Undated")
+                    );
+                }
+                if let Some(month) = &p_group.1 {
+                    let _ = write!(html, "<{month_heading}>{month}</{month_heading}>");
+                }
+                html.push_str("<ul>");
+                group = Some(p_group);
+            }
+
+            html.push_str("<li>");
+            if let Some(date) = p.date {
+                let _ = write!(
+                    html,
+                    r#"<time datetime="{}">{}</time>: "#,
+                    date.to_rfc3339(),
+                    date.format("%Y-%m-%d"),
+                );
+            }
+            let _ = write!(
+                html,
+                "{}{}</a>",
+                page::local_link(HtmlEscape(p.path())),
+                page::title_html(&p.title),
+            );
+            if let Some(description) = &p.description {
+                let _ = write!(html, "<p>{}</p>", HtmlEscape(description));
+            }
+            html.push_str("</li>");
+        }
+        if group.is_some() {
+            html.push_str("</ul>");
+        }
+
+        if self.page_count() > 1 {
+            html.push_str(r#"<nav class="pagination">"#);
+            if n > 1 {
+                let _ = write!(
+                    html,
+                    "{}Newer</a> ",
+                    page::local_link(HtmlEscape(self.page_path(n - 1)))
+                );
+            }
+            let _ = write!(html, "<span>{n}/{}</span>", self.page_count());
+            if n < self.page_count() {
+                let _ = write!(
+                    html,
+                    " {}Older</a>",
+                    page::local_link(HtmlEscape(self.page_path(n + 1)))
+                );
+            }
+            html.push_str("</nav>");
+        }
+
+        html.push_str("</div>");
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    fn post(rel_path: &str, date: &str) -> site::Page {
+        let doc = Org::parse(format!(
+            ":PROPERTIES:\n:publication_date: {date}\n:END:\n#+title: {rel_path}\n"
+        ));
+        site::Page::new(&doc, &[], Path::new(rel_path))
+    }
+
+    #[gtest]
+    fn options() {
+        let path = Path::new("b.org");
+        expect_that!(Options::parse("t", path), eq(Options::default()));
+        expect_that!(
+            Options::parse(":scope siblings :per_page 2", path),
+            eq(Options {
+                scope: Scope::Siblings,
+                per_page: Some(2),
+            })
+        );
+        expect_that!(Options::parse(":per_page 0", path).per_page, none());
+
+        let doc = Org::parse("* Posts\n:PROPERTIES:\n:generate_index: :per_page 5\n:END:\n");
+        expect_that!(
+            Options::find(&doc, path).and_then(|o| o.per_page),
+            some(eq(5))
+        );
+        let doc = Org::parse("#+generate_index: :scope siblings\n");
+        expect_that!(
+            Options::find(&doc, path).map(|o| o.scope),
+            some(eq(Scope::Siblings))
+        );
+        expect_that!(Options::find(&Org::parse("* Posts\n"), path), none());
+    }
+
+    #[gtest]
+    fn grouped_and_paginated() {
+        let mut site = site::Site::new();
+        site.push(post("b.org", "2025-01-01"));
+        site.push(post("b/a.org", "2024-12-24"));
+        site.push(post("b/b.org", "2025-01-22"));
+        site.push(post("b/c.org", "2025-01-02"));
+        site.push(post("b/old/d.org", "2025-02-01"));
+        site.push(post("about.org", "2025-03-01"));
+
+        let options = Options {
+            scope: Scope::Descendants,
+            per_page: Some(3),
+        };
+        let archive = Archive::new(&site, Path::new("b.org"), options);
+        expect_that!(archive.page_count(), eq(2));
+        expect_that!(archive.page_path(2), eq("/b/page/2"));
+
+        let first = archive.to_html(1, 3);
+        expect_that!(
+            first,
+            all![
+                starts_with(r#"<div class="archive"><h3>2025</h3><h4>February</h4><ul><li>"#),
+                contains_substring(r#"</ul><h4>January</h4><ul><li>"#),
+                contains_substring("/b/old/d/_.html"),
+                not(contains_substring("/b/a/_.html")),
+                not(contains_substring("/about/")),
+                contains_substring(r#"<span>1/2</span> <a hx-get="/b/page/2/_.html""#),
+            ]
+        );
+        let b_at = first.find("/b/b/_.html").unwrap();
+        let c_at = first.find("/b/c/_.html").unwrap();
+        expect_that!(b_at, lt(c_at));
+
+        let second = archive.to_html(2, 3);
+        expect_that!(
+            second,
+            all![
+                starts_with(r#"<div class="archive"><h3>2024</h3><h4>December</h4><ul><li>"#),
+                contains_substring("/b/a/_.html"),
+                contains_substring(r#"<a hx-get="/b/_.html""#),
+            ]
+        );
+
+        let siblings = Archive::new(
+            &site,
+            Path::new("b/a.org"),
+            Options {
+                scope: Scope::Siblings,
+                per_page: None,
+            },
+        );
+        expect_that!(siblings.page_count(), eq(1));
+        expect_that!(
+            siblings.to_html(1, 2),
+            all![
+                contains_substring("/b/b/_.html"),
+                contains_substring("/b/c/_.html"),
+                not(contains_substring("/b/a/_.html")),
+                not(contains_substring("/b/old/d/_.html")),
+                not(contains_substring("pagination")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use orgize::ast::Link;
+use orgize::export::HtmlEscape;
+use orgize::rowan::ast::AstNode;
+use orgize::{Org, SyntaxElement, SyntaxKind, SyntaxNode};
+
+use crate::links::Target;
+use crate::page;
+use crate::site::{Page, Site};
+
+/// A page linking to another one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backlink {
+    /// Path of the linking `.org` file, relative to the site directory
+    pub rel_path: PathBuf,
+    /// Paragraphs with the links, as HTML
+    pub contexts: Vec<String>,
+}
+
+/// Pages linked from `doc`, by path or `id:`, and the paragraph of each link.
+///
+/// Only listed pages link to others, drafts don't.
+pub fn links_from(doc: &Org, rel_path: &Path, site: &Site) -> Vec<(PathBuf, String)> {
+    if !site.pages().any(|p| p.rel_path == rel_path) {
+        return vec![];
+    }
+
+    let mut links = vec![];
+    for link in doc.document().syntax().descendants().filter_map(Link::cast) {
+        let Target::Page { page, .. } = site.target(rel_path, &link) else {
+            continue;
+        };
+        if page.rel_path == rel_path {
+            continue;
+        }
+        let node = link.syntax();
+        let Some(context) = node
+            .ancestors()
+            .find(|n| n.kind() == SyntaxKind::PARAGRAPH)
+            .or_else(|| node.parent())
+        else {
+            continue;
+        };
+        links.push((
+            page.rel_path.clone(),
+            context_html(&context, node, site, rel_path),
+        ));
+    }
+    links
+}
+
+/// "Linked from" section, with the pages linking to `rel_path`, newest first.
+pub fn to_html(site: &Site, rel_path: &Path, depth: i8) -> String {
+    let mut backlinks: Vec<(&Backlink, &Page)> = site
+        .backlinks(rel_path)
+        .iter()
+        .filter_map(|b| Some((b, site.get(&b.rel_path)?)))
+        .collect();
+    if backlinks.is_empty() {
+        return String::new();
+    }
+    backlinks.sort_by(|(_, a), (_, b)| (b.date, &a.rel_path).cmp(&(a.date, &b.rel_path)));
+
+    let heading = page::heading(depth);
+    let mut html = format!(r#"<section class="backlinks"><{heading}>Linked from</{heading}><ul>"#);
+    for (backlink, page) in backlinks {
+        let _ = write!(
+            html,
+            "<li>{}{}</a>",
+            page::local_link(HtmlEscape(page.path())),
+            page::title_html(&page.title),
+        );
+        for context in &backlink.contexts {
+            let _ = write!(html, "<blockquote><p>{context}</p></blockquote>");
+        }
+        html.push_str("</li>");
+    }
+    html.push_str("</ul></section>");
+    html
+}
+
+/// Text of `context`, with `link` highlighted. Links without a description
+/// read as the title of their target, like on the page.
+fn context_html(context: &SyntaxNode, link: &SyntaxNode, site: &Site, rel_path: &Path) -> String {
+    let mut html = String::new();
+    push_text(&mut html, context, link, site, rel_path);
+    html.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn push_text(
+    html: &mut String,
+    node: &SyntaxNode,
+    link: &SyntaxNode,
+    site: &Site,
+    rel_path: &Path,
+) {
+    for element in node.children_with_tokens() {
+        match element {
+            SyntaxElement::Token(token) => {
+                if matches!(
+                    token.kind(),
+                    SyntaxKind::TEXT | SyntaxKind::WHITESPACE | SyntaxKind::NEW_LINE
+                ) {
+                    let _ = write!(html, "{}", HtmlEscape(token.text()));
+                }
+            }
+            SyntaxElement::Node(node) if node.kind() == SyntaxKind::AFFILIATED_KEYWORD => {}
+            SyntaxElement::Node(node) => match Link::cast(node.clone()) {
+                Some(other) => {
+                    let text = if other.has_description() {
+                        node.descendants_with_tokens()
+                            .filter(|e| e.kind() == SyntaxKind::TEXT)
+                            .map(|e| e.to_string())
+                            .collect()
+                    } else {
+                        match site.target(rel_path, &other) {
+                            Target::Page {
+                                section: Some((_, title)),
+                                ..
+                            } => title.to_string(),
+                            Target::Page { page, .. } => page.title.clone(),
+                            _ => other.path().to_string(),
+                        }
+                    };
+                    if node == *link {
+                        let _ = write!(html, "<mark>{}</mark>", HtmlEscape(text));
+                    } else {
+                        let _ = write!(html, "{}", HtmlEscape(text));
+                    }
+                }
+                None => push_text(html, &node, link, site, rel_path),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use indoc::indoc;
+
+    #[gtest]
+    fn linked_from() {
+        let (mut site, docs) = crate::site::tests::site(&[
+            (
+                "about.org",
+                ":PROPERTIES:\n:ID: about-id\n:END:\n#+title: About\n",
+            ),
+            (
+                "b/post.org",
+                indoc! {r###"
+                  #+title: Post
+                  Written by *me*, see [[../about.org][who]].
+
+                  Also [[id:about-id]] and [[./post.org][itself]].
+                "###},
+            ),
+            (
+                "b/draft.org",
+                ":PROPERTIES:\n:Draft: t\n:END:\nBy [[/about.org]].\n",
+            ),
+        ]);
+        for (rel_path, doc) in &docs {
+            let links = links_from(doc, rel_path, &site);
+            site.push_backlinks(rel_path, links);
+        }
+
+        expect_that!(
+            to_html(&site, Path::new("about.org"), 2),
+            all![
+                starts_with(
+                    r#"<section class="backlinks"><h2>Linked from</h2><ul><li><a hx-get="/b/post/_.html""#
+                ),
+                contains_substring(
+                    "Post</a><blockquote><p>Written by me, see <mark>who</mark>.</p></blockquote>"
+                ),
+                contains_substring(
+                    "<blockquote><p>Also <mark>About</mark> and itself.</p></blockquote></li>"
+                ),
+                not(contains_substring("/b/draft/")),
+            ]
+        );
+        expect_that!(to_html(&site, Path::new("b/post.org"), 2), eq(""));
+    }
+}
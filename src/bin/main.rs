@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fs;
 use std::io;
 use std::path::Path;
@@ -8,8 +9,18 @@ use clap::Parser;
 use orgize::Org;
 use walkdir::WalkDir;
 
+use blorg::archive;
+use blorg::backlinks;
+use blorg::check;
 use blorg::feed;
+use blorg::figure;
+use blorg::highlight;
+use blorg::images;
 use blorg::page;
+use blorg::publish;
+use blorg::site;
+use blorg::tags;
+use blorg::tangle;
 
 /// Command line arguments
 #[derive(Parser, Debug)]
@@ -18,6 +29,15 @@ pub struct Args {
     // Input
     #[arg(default_value = "site", env = "BLOG_DIR")]
     pub dir: PathBuf,
+    /// Publish only the pages reached following links from this `.org` file, relative to `dir`
+    #[arg(long = "entry")]
+    pub entries: Vec<PathBuf>,
+    /// Like `--entry`, for every page with this tag
+    #[arg(long = "entry-tag")]
+    pub entry_tags: Vec<String>,
+    /// Tag allowing pages reached from the entries to be published, besides a `publish` property
+    #[arg(long = "publish-tag")]
+    pub publish_tags: Vec<String>,
 
     // Configuration
     #[arg(long, env = "BLOG_ROOT_ADDRESS")]
@@ -26,6 +46,9 @@ pub struct Args {
     pub title: String,
     #[arg(long, env = "BLOG_DESCRIPTION")]
     pub description: String,
+    /// Default author for pages without an `author` property or `#+author:` keyword
+    #[arg(long, env = "BLOG_AUTHOR")]
+    pub author: Option<String>,
     #[arg(long, default_value = "en-GB")]
     pub language: String,
 
@@ -38,6 +61,27 @@ pub struct Args {
 
     #[arg(long, default_value = "false")]
     pub copy_older_files: bool,
+    /// Image to resize besides the ones shown on pages, like a stylesheet background, relative to `dir`
+    #[arg(long = "resize", env = "BLOG_RESIZE", value_delimiter = ',')]
+    pub resized: Vec<PathBuf>,
+
+    /// Embed the whole page on RSS and Atom feed entries
+    #[arg(long, default_value = "false")]
+    pub feed_full_content: bool,
+    /// Maximum number of entries on each feed, newest first
+    #[arg(long)]
+    pub feed_max_entries: Option<usize>,
+
+    /// Syntax highlighting theme for source blocks
+    #[arg(long, default_value = highlight::DEFAULT_THEME, env = "BLOG_HIGHLIGHT_THEME")]
+    pub highlight_theme: String,
+
+    /// Check the links of the generated pages after building, failing if any is broken
+    #[arg(long, default_value = "false")]
+    pub check_links: bool,
+    /// External URL prefixes for `--check-links` to allow, one per line, or deny with a leading `!`
+    #[arg(long, env = "BLOG_URL_LIST")]
+    pub url_list: Option<PathBuf>,
 
     #[arg(long, default_value = "false")]
     pub minify_html: bool,
@@ -45,6 +89,15 @@ pub struct Args {
     pub minifier_copy_on_failure: bool,
 }
 
+/// An org file to render
+struct OrgFile {
+    rel_path: PathBuf,
+    /// Directory for the stub and fragment
+    out_path: PathBuf,
+    doc: Org,
+    tags: Vec<String>,
+}
+
 fn setup_logger(args: &Args) -> Result<(), fern::InitError> {
     fern::Dispatch::new()
         .format(|out, message, record| {
@@ -66,7 +119,7 @@ fn setup_logger(args: &Args) -> Result<(), fern::InitError> {
 fn try_mkdir(path: &Path) -> io::Result<()> {
     if !fs::exists(path)? {
         log::info!("Creating directory '{}'", path.display());
-        fs::create_dir(path)?;
+        fs::create_dir_all(path)?;
     }
     Ok(())
 }
@@ -101,6 +154,9 @@ fn write_stub_file(args: &Args, path: &Path) -> io::Result<()> {
             <link rel="preload" href="_.html" as="fetch" type="text/html" crossorigin />
             <link rel="preload" href="/s/_.css" as="style" type="text/css" crossorigin />
             <link rel="preload" href="/l/_.js" as="script" type="text/javascript" crossorigin />
+            <link rel="alternate" href="/feed.atom" type="application/atom+xml" title="{title}" />
+            <link rel="alternate" href="/feed.rss" type="application/rss+xml" title="{title}" />
+            <link rel="alternate" href="/feed.json" type="application/feed+json" title="{title}" />
             <script type="text/javascript">
         const l = window.location;
         var url = new URL(window.location);
@@ -121,6 +177,13 @@ fn write_stub_file(args: &Args, path: &Path) -> io::Result<()> {
     write_html(args, path, &contents)
 }
 
+/// Writes a generated page on `out_path/`, with its stub and `_.html` fragment.
+fn write_page(args: &Args, out_path: &Path, html: &str) -> io::Result<()> {
+    fs::create_dir_all(out_path)?;
+    write_stub_file(args, &out_path.join("index.html"))?;
+    write_html(args, &out_path.join("_.html"), html)
+}
+
 /// Writes an HTML file. May minify the file.
 fn write_html(args: &Args, path: &Path, contents: &str) -> io::Result<()> {
     if args.minify_html {
@@ -233,6 +296,17 @@ fn main() -> io::Result<()> {
     let args = Args::parse();
     setup_logger(&args).map_err(|_| io::Error::other("Failed to setup logging"))?;
 
+    let Some(highlight_css) = highlight::css(&args.highlight_theme) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Unknown highlight theme '{}'. Use one of: {}",
+                args.highlight_theme,
+                highlight::themes().collect::<Vec<_>>().join(", ")
+            ),
+        ));
+    };
+
     let now = chrono::Local::now();
 
     let mut sitemap = feed::Sitemap::new(
@@ -241,8 +315,16 @@ fn main() -> io::Result<()> {
         args.description.clone(),
         args.language.clone(),
         now,
-    );
-
+    )
+    .full_content(args.feed_full_content)
+    .max_entries(args.feed_max_entries)
+    .author(args.author.clone());
+    let mut site = site::Site::new().dir(&args.dir);
+    let mut org_files: Vec<OrgFile> = vec![];
+
+    // Parse the org files, the rest are copied once the pages are known
+    let mut all_org_files: Vec<PathBuf> = vec![];
+    let mut static_files: Vec<PathBuf> = vec![];
     for path in WalkDir::new(&args.dir)
         .same_file_system(true)
         .min_depth(1)
@@ -251,82 +333,243 @@ fn main() -> io::Result<()> {
         .filter_map(|e| e.ok().map(|e| e.into_path()))
     {
         log::debug!("Processing '{}'", path.display());
-        let rel_path: &Path = path.strip_prefix(&args.dir).unwrap();
-        let mut out_path: PathBuf = args.outdir.clone();
-        out_path.push(rel_path);
-
         if path.is_dir() {
-            try_mkdir(&out_path)?;
             continue;
         }
-
+        let rel_path: &Path = path.strip_prefix(&args.dir).unwrap();
         let file_name = rel_path.file_name().unwrap().to_str().unwrap();
 
         if file_should_be_skipped(file_name) {
             log::info!("Skipping {rel_path:?}");
             continue;
         }
+        if path.extension().and_then(|s| s.to_str()) != Some("org") {
+            static_files.push(rel_path.to_path_buf());
+            continue;
+        }
 
-        match path.extension().and_then(|s| s.to_str()) {
-            Some("org") => {
-                log::info!("Generating '{}'...", out_path.display());
-                let contents =
-                    fs::read_to_string(&path).expect("Should have been able to read the file");
+        log::debug!("Parsing '{}'...", path.display());
+        let contents = fs::read_to_string(&path).expect("Should have been able to read the file");
 
-                let doc = Org::parse(&contents);
-                let tags = page::org_tags(&doc, &contents);
+        let doc = Org::parse(&contents);
+        let tags = page::org_tags(&doc, &contents);
+        // Even when skipped, so links to it are reported as unpublished
+        site.index(&doc, rel_path);
+        all_org_files.push(rel_path.to_path_buf());
 
-                if org_should_be_skipped(&doc, &contents, &tags) {
-                    log::info!("Skipping {rel_path:?} because of its tags");
-                    continue;
-                }
+        if org_should_be_skipped(&doc, &contents, &tags) {
+            log::info!("Skipping {rel_path:?} because of its tags");
+            continue;
+        }
+
+        let mut out_path: PathBuf = args.outdir.join(rel_path);
+        out_path.set_extension("");
+        site.push(site::Page::new(&doc, &tags, rel_path));
+        org_files.push(OrgFile {
+            rel_path: rel_path.to_path_buf(),
+            out_path,
+            doc,
+            tags,
+        });
+    }
+
+    // Links can only be resolved once every page is known
+    for file in &org_files {
+        site.resolve_links(&file.doc, &file.rel_path);
+    }
+
+    let subgraph = publish::Subgraph::new()
+        .entries(&args.entries)
+        .entry_tags(&args.entry_tags)
+        .publish_tags(&args.publish_tags);
+    if subgraph.is_enabled() {
+        let selected = subgraph.select(&site);
+        log::info!(
+            "Publishing {} of {} pages reached from the entry points",
+            selected.len(),
+            org_files.len()
+        );
+        let files = publish::Files::new(&site, &all_org_files, &selected);
+        static_files.retain(|rel_path| {
+            let published = files.contains(rel_path);
+            if !published {
+                log::info!("Skipping {rel_path:?} as only unpublished pages link to it");
+            }
+            published
+        });
+        site.retain(|p| selected.contains(&p.rel_path));
+        org_files.retain(|f| selected.contains(&f.rel_path));
+    }
+
+    // Only images shown on pages, or asked for, get resized variants
+    let mut resized: HashSet<PathBuf> = HashSet::new();
+    for rel_path in &args.resized {
+        if !static_files.contains(rel_path) {
+            log::warn!("Can't find {rel_path:?} to resize on the site");
+        }
+        resized.insert(rel_path.clone());
+    }
+    for file in &org_files {
+        resized.extend(figure::images(&file.doc, &site, &file.rel_path));
+    }
+
+    for rel_path in &static_files {
+        write_static(
+            &args,
+            &args.dir.join(rel_path),
+            &args.outdir.join(rel_path),
+            resized.contains(rel_path),
+        )?;
+    }
+
+    for file in &org_files {
+        let links = backlinks::links_from(&file.doc, &file.rel_path, &site);
+        site.push_backlinks(&file.rel_path, links);
+    }
+
+    // Site paths of the pages generated beside the org ones, like tag pages
+    let mut generated: Vec<String> = vec![];
+
+    // Render the org files now that we know about every page
+    for file in &org_files {
+        let out_path = &file.out_path;
+        log::info!("Generating '{}'...", out_path.display());
+
+        try_mkdir(out_path)?;
+        log::debug!(
+            "Generating index.html redirect for '{}'...",
+            out_path.display()
+        );
+        write_stub_file(&args, &out_path.join("index.html"))?;
+
+        let html = page::to_html(&file.doc, &file.tags, &file.rel_path, &site)?;
+        sitemap.push(&file.doc, &file.tags, &file.rel_path, &html);
+
+        // Write HTML fragment
+        log::debug!(
+            "Generating HTML fragment (_.html) for '{}'...",
+            out_path.display()
+        );
+        write_html(&args, &out_path.join("_.html"), &html)?;
+
+        for (target, contents) in tangle::tangle(&file.doc, &file.rel_path) {
+            let target = out_path.join(target);
+            log::info!("Tangling '{}'...", target.display());
+            fs::create_dir_all(target.parent().unwrap())?;
+            fs::write(target, contents)?;
+        }
 
-                out_path.set_extension("");
-                try_mkdir(&out_path)?;
-                log::debug!(
-                    "Generating index.html redirect for '{}'...",
-                    out_path.display()
-                );
-                write_stub_file(&args, &out_path.join("index.html"))?;
-
-                sitemap.push(&doc, rel_path);
-                let html = page::to_html(doc, &tags, rel_path)?;
-
-                // Write HTML fragment
-                log::debug!(
-                    "Generating HTML fragment (_.html) for '{}'...",
-                    out_path.display()
-                );
-                out_path.push("_.html");
-                write_html(&args, &out_path, &html)?;
+        // Write the rest of the archive, the first page is already on the fragment
+        if let Some(options) = archive::Options::find(&file.doc, &file.rel_path) {
+            let archive = archive::Archive::new(&site, &file.rel_path, options);
+            let title = page::title_html(&site.get(&file.rel_path).unwrap().title);
+            for n in 2..=archive.page_count() {
+                let html = format!("<h1>{title}</h1>{}", archive.to_html(n, 2));
+                write_page(&args, &out_path.join("page").join(n.to_string()), &html)?;
+                sitemap.push_subpage(&file.doc, &archive.page_path(n));
+                generated.push(archive.page_path(n));
             }
-            Some("html") => write_html(&args, &out_path, &fs::read_to_string(path)?)?,
-            Some("css") => write_css(&args, &out_path, &fs::read_to_string(path)?)?,
-            Some("js") => write_js(&args, &out_path, &fs::read_to_string(path)?)?,
-            Some(_ext) => {
-                if !args.copy_older_files && fs::exists(&out_path)? {
-                    // Get metadata
-                    let new_file = fs::metadata(&path)?;
-                    let old_file = fs::metadata(&out_path)?;
-
-                    if (new_file.len() == old_file.len())
-                        && (new_file.modified()? <= old_file.modified()?)
-                    {
-                        log::debug!(
-                            "Skipping writing of up-to-date file '{}'",
-                            out_path.display()
-                        );
-                        continue;
-                    }
+        }
+    }
+
+    let tag_index = tags::Tags::from_site(&site);
+    let tags_out_path = args.outdir.join(tags::TAGS_DIR);
+    log::info!("Generating tag pages on '{}'...", tags_out_path.display());
+    write_page(&args, &tags_out_path, &tag_index.overview_html())?;
+    generated.push(format!("/{}", tags::TAGS_DIR));
+    for tag in tag_index.names() {
+        write_page(&args, &tags_out_path.join(tag), &tag_index.tag_html(tag))?;
+        generated.push(format!("/{}/{tag}", tags::TAGS_DIR));
+    }
+
+    let highlight_css_path = args.outdir.join(highlight::CSS_PATH);
+    fs::create_dir_all(highlight_css_path.parent().unwrap())?;
+    write_css(&args, &highlight_css_path, &highlight_css)?;
+
+    sitemap.generate(&args.outdir)?;
+
+    if args.check_links {
+        check_links(&args, &site, &org_files, &generated)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a file that isn't a page, like a stylesheet or an image, to `out_path`.
+///
+/// Images also get their resized variants when `resize`.
+fn write_static(args: &Args, path: &Path, out_path: &Path, resize: bool) -> io::Result<()> {
+    if let Some(parent) = out_path.parent() {
+        try_mkdir(parent)?;
+    }
+
+    match path.extension().and_then(|s| s.to_str()) {
+        Some("html") => write_html(args, out_path, &fs::read_to_string(path)?),
+        Some("css") => write_css(args, out_path, &fs::read_to_string(path)?),
+        Some("js") => write_js(args, out_path, &fs::read_to_string(path)?),
+        Some(_ext) => {
+            if resize && images::is_raster(path) {
+                images::generate(path, out_path, args.copy_older_files)?;
+            }
+
+            if !args.copy_older_files && fs::exists(out_path)? {
+                // Get metadata
+                let new_file = fs::metadata(path)?;
+                let old_file = fs::metadata(out_path)?;
+
+                if (new_file.len() == old_file.len())
+                    && (new_file.modified()? <= old_file.modified()?)
+                {
+                    log::debug!(
+                        "Skipping writing of up-to-date file '{}'",
+                        out_path.display()
+                    );
+                    return Ok(());
                 }
-                log::info!("Will write '{}'", out_path.display());
-                fs::copy(path, out_path)?;
             }
-            _ => {}
+            log::info!("Will write '{}'", out_path.display());
+            fs::copy(path, out_path)?;
+            Ok(())
         }
+        None => Ok(()),
     }
+}
 
-    sitemap.generate(&args.outdir);
+/// Reports the broken links of the org pages and the `generated` ones,
+/// failing if there's any.
+fn check_links(
+    args: &Args,
+    site: &site::Site,
+    org_files: &[OrgFile],
+    generated: &[String],
+) -> io::Result<()> {
+    let urls = match &args.url_list {
+        Some(path) => Some(check::UrlList::parse(&fs::read_to_string(path)?)),
+        None => None,
+    };
+    let mut checker = check::Checker::new(site, &args.outdir).urls(urls);
+
+    log::info!("Checking links...");
+    let mut broken = 0;
+    for file in org_files {
+        let html = fs::read_to_string(file.out_path.join("_.html"))?;
+        for link in checker.check(&file.doc, &file.rel_path, &html) {
+            log::error!("Broken link: {link}");
+            broken += 1;
+        }
+    }
+    for path in generated {
+        let out_path = args.outdir.join(path.trim_start_matches('/'));
+        let html = fs::read_to_string(out_path.join("_.html"))?;
+        for link in checker.check_generated(path, &html) {
+            log::error!("Broken link: {link}");
+            broken += 1;
+        }
+    }
 
+    if broken > 0 {
+        return Err(io::Error::other(format!("Found {broken} broken links")));
+    }
+    log::info!("No broken links");
     Ok(())
 }
@@ -0,0 +1,361 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use orgize::Org;
+use orgize::rowan::ast::AstNode;
+use regex::Regex;
+
+use crate::links::{self, Target};
+use crate::page;
+use crate::site::Site;
+
+/// Attributes linking to other files, and their value
+static URL_ATTRIBUTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\s(href|src|srcset|hx-get)="([^"]*)""#).expect("Valid regex"));
+
+/// `id` attributes, the targets of `#anchor` links
+static ID_ATTRIBUTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\sid="([^"]*)""#).expect("Valid regex"));
+
+/// A link that doesn't lead anywhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// Path of the `.org` file linking, relative to the site directory, or
+    /// of the generated page, relative to the output directory
+    pub rel_path: PathBuf,
+    /// Line of the link on the `.org` file, if found
+    pub line: Option<usize>,
+    pub target: String,
+    pub reason: String,
+}
+
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}", self.rel_path.display())?,
+            None => write!(f, "{}", self.rel_path.display())?,
+        }
+        write!(f, ": '{}' {}", self.target, self.reason)
+    }
+}
+
+/// External URLs allowed and denied by prefix, to check them without fetching.
+///
+/// One prefix per line, denied ones start with `!`. Lines starting with `#`
+/// are comments. Denied prefixes win, and once any prefix is allowed every
+/// other URL is reported.
+#[derive(Debug, Default)]
+pub struct UrlList {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl UrlList {
+    pub fn parse(contents: &str) -> Self {
+        let mut list = Self::default();
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.strip_prefix('!') {
+                Some(denied) => list.deny.push(denied.trim().to_string()),
+                None => list.allow.push(line.to_string()),
+            }
+        }
+        list
+    }
+
+    /// Why `url` isn't allowed, if it isn't
+    fn reject(&self, url: &str) -> Option<&'static str> {
+        if self.deny.iter().any(|p| url.starts_with(p.as_str())) {
+            Some("is denied by the URL list")
+        } else if !self.allow.is_empty() && !self.allow.iter().any(|p| url.starts_with(p.as_str()))
+        {
+            Some("isn't allowed by the URL list")
+        } else {
+            None
+        }
+    }
+}
+
+/// Checks the links of the pages written on an output directory.
+pub struct Checker<'a> {
+    site: &'a Site,
+    outdir: &'a Path,
+    urls: Option<UrlList>,
+    /// `id`s of the fragments read so far, by their path on the output directory
+    ids: HashMap<PathBuf, HashSet<String>>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(site: &'a Site, outdir: &'a Path) -> Self {
+        Self {
+            site,
+            outdir,
+            urls: None,
+            ids: HashMap::new(),
+        }
+    }
+
+    /// Checks external URLs against `urls`. They aren't checked otherwise.
+    pub fn urls(mut self, urls: Option<UrlList>) -> Self {
+        self.urls = urls;
+        self
+    }
+
+    /// Broken links of the page rendered from `doc` into `html`.
+    ///
+    /// Org links are resolved and looked up on the output directory, along
+    /// with the section they point to, and reported on their line. Then every
+    /// other `href`, `src`, `srcset` and `hx-get` of `html` is looked up too.
+    pub fn check(&mut self, doc: &Org, rel_path: &Path, html: &str) -> Vec<BrokenLink> {
+        let source = doc.document().syntax().to_string();
+        let page_url = self
+            .site
+            .get(rel_path)
+            .map(|p| format!("{}/", p.path()))
+            .unwrap_or_else(|| String::from("/"));
+        let mut broken = vec![];
+        let mut checked = HashSet::new();
+
+        for link in self.site.links(rel_path) {
+            let path = &link.path;
+            let (url, reason) = match link.target(self.site) {
+                Target::Broken => (
+                    path.to_string(),
+                    Some("links to a missing or unpublished page"),
+                ),
+                Target::External => {
+                    let reason = self.urls.as_ref().and_then(|list| list.reject(path));
+                    (path.to_string(), reason)
+                }
+                Target::Asset(src) => {
+                    let reason = self.check_url(&src, &page_url, html);
+                    (src, reason)
+                }
+                Target::Page { page, section } => {
+                    let url = match section {
+                        Some((anchor, _)) => format!("{}/#{anchor}", page.path()),
+                        None => format!("{}/", page.path()),
+                    };
+                    let reason = self.check_url(&url, &page_url, html);
+                    (url, reason)
+                }
+            };
+            checked.insert(url);
+            if let Some(reason) = reason {
+                broken.push(BrokenLink {
+                    rel_path: rel_path.to_path_buf(),
+                    line: Some(link.line),
+                    target: path.to_string(),
+                    reason: String::from(reason),
+                });
+            }
+        }
+
+        broken.extend(self.check_html(rel_path, &page_url, &source, html, checked));
+        broken
+    }
+
+    /// Broken links of a page generated by the build, like a tag page or the
+    /// rest of an archive, at the site path `path`.
+    pub fn check_generated(&mut self, path: &str, html: &str) -> Vec<BrokenLink> {
+        let path = path.trim_matches('/');
+        self.check_html(
+            Path::new(path),
+            &format!("/{path}/"),
+            "",
+            html,
+            HashSet::new(),
+        )
+    }
+
+    /// Looks up every `href`, `src`, `srcset` and `hx-get` of `html`, the page
+    /// at `page_url`, on the output directory, but the `checked` URLs. Broken
+    /// ones are found on `source`, the `.org` text of the page.
+    fn check_html(
+        &mut self,
+        rel_path: &Path,
+        page_url: &str,
+        source: &str,
+        html: &str,
+        mut checked: HashSet<String>,
+    ) -> Vec<BrokenLink> {
+        let mut broken = vec![];
+        for captures in URL_ATTRIBUTE.captures_iter(html) {
+            let value = page::unescape(&captures[2]);
+            let urls: Vec<&str> = if &captures[1] == "srcset" {
+                value
+                    .split(',')
+                    .filter_map(|c| c.split_whitespace().next())
+                    .collect()
+            } else {
+                vec![value.as_ref()]
+            };
+
+            for url in urls {
+                if !checked.insert(url.to_string()) {
+                    continue;
+                }
+                let reason = if links::is_external(url) {
+                    self.urls.as_ref().and_then(|list| list.reject(url))
+                } else {
+                    self.check_url(url, page_url, html)
+                };
+                if let Some(reason) = reason {
+                    broken.push(BrokenLink {
+                        rel_path: rel_path.to_path_buf(),
+                        line: find_line(source, url),
+                        target: url.to_string(),
+                        reason: String::from(reason),
+                    });
+                }
+            }
+        }
+        broken
+    }
+
+    /// Why the site `url`, on the page at `page_url`, is broken, if it is
+    fn check_url(&mut self, url: &str, page_url: &str, html: &str) -> Option<&'static str> {
+        let (path, anchor) = match url.split_once('#') {
+            Some((path, anchor)) => (path, Some(anchor)),
+            None => (url, None),
+        };
+        if path.is_empty() {
+            let anchor = anchor.filter(|a| !a.is_empty())?;
+            let found = ID_ATTRIBUTE
+                .captures_iter(html)
+                .any(|c| page::unescape(&c[1]) == anchor);
+            return (!found).then_some("has no matching section on the page");
+        }
+
+        let url_path = match path.strip_prefix('/') {
+            Some(path) => path.to_string(),
+            None => format!("{}{path}", page_url.trim_start_matches('/')),
+        };
+        let file = self.outdir.join(url_path.trim_end_matches('/'));
+        // Pages are directories, their fragment has the sections
+        let (file, fragment) = if file.is_dir() {
+            (file.join("index.html"), file.join("_.html"))
+        } else {
+            (file.clone(), file)
+        };
+        if !file.is_file() {
+            return Some("doesn't exist on the site");
+        }
+
+        let anchor = anchor.filter(|a| !a.is_empty())?;
+        let ids = self.ids.entry(fragment.clone()).or_insert_with(|| {
+            fs::read_to_string(&fragment)
+                .map(|html| {
+                    ID_ATTRIBUTE
+                        .captures_iter(&html)
+                        .map(|c| page::unescape(&c[1]).into_owned())
+                        .collect()
+                })
+                .unwrap_or_default()
+        });
+        (!ids.contains(anchor)).then_some("has no matching section on its page")
+    }
+}
+
+/// Line of the `.org` source with `url` as is. A guess would be worse than
+/// no line.
+fn find_line(source: &str, url: &str) -> Option<usize> {
+    source
+        .lines()
+        .position(|l| l.contains(url))
+        .map(|line| line + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::site;
+    use googletest::prelude::*;
+    use indoc::indoc;
+
+    #[gtest]
+    fn url_lists() {
+        let list = UrlList::parse(indoc! {"
+          # Known sites
+          https://example.com/
+          !https://example.com/private
+        "});
+        expect_that!(list.reject("https://example.com/a"), none());
+        expect_that!(
+            list.reject("https://example.com/private/a"),
+            some(anything())
+        );
+        expect_that!(list.reject("https://other.com/"), some(anything()));
+        expect_that!(UrlList::default().reject("https://other.com/"), none());
+    }
+
+    #[gtest]
+    fn broken_links() -> Result<()> {
+        let outdir = std::env::temp_dir().join(format!("blorg-check-{}", std::process::id()));
+        fs::create_dir_all(outdir.join("about"))?;
+        fs::write(outdir.join("about/index.html"), "")?;
+        fs::write(outdir.join("about/_.html"), r#"<section id="contact">"#)?;
+
+        let (site, docs) = site::tests::site(&[
+            (
+                "b.org",
+                indoc! {r###"
+              See [[/about.org]] and the
+              [[./renamed.org][old post]].
+              [[https://example.com/]]
+              [[/about.org::*Other][Other]] and [[./gone.pdf]].
+              [[/about.org::*Contact][Contact]]
+            "###},
+            ),
+            ("about.org", "#+title: About\n* Contact\n* Other\n"),
+        ]);
+        let (rel_path, doc) = &docs[0];
+        let html = indoc! {r##"
+          <a hx-get="/about/_.html" href="/about/">About</a>
+          <a class="broken-link">old post</a>
+          <a href="https://example.com/">Example</a>
+          <a href="/about/#other">Other</a> and <a href="/gone.pdf">./gone.pdf</a>.
+          <a href="/about/#contact">Contact</a> <a href="/about/#missing">Missing</a>
+          <a href="#top">Top</a><img src="/i/gone.png" srcset="/about/ 1w, /i/gone-480w.png 480w">
+        "##};
+        let urls = UrlList::parse("!https://example.com/");
+        let mut checker = Checker::new(&site, &outdir).urls(Some(urls));
+        let broken: Vec<String> = checker
+            .check(doc, rel_path, html)
+            .iter()
+            .map(|b| b.to_string())
+            .collect();
+        expect_that!(
+            broken,
+            elements_are![
+                eq("b.org:2: './renamed.org' links to a missing or unpublished page"),
+                eq("b.org:3: 'https://example.com/' is denied by the URL list"),
+                eq("b.org:4: '/about.org::*Other' has no matching section on its page"),
+                eq("b.org:4: './gone.pdf' doesn't exist on the site"),
+                eq("b.org: '/about/#missing' has no matching section on its page"),
+                eq("b.org: '#top' has no matching section on the page"),
+                eq("b.org: '/i/gone.png' doesn't exist on the site"),
+                eq("b.org: '/i/gone-480w.png' doesn't exist on the site"),
+            ]
+        );
+        expect_that!(
+            checker
+                .check_generated(
+                    "/tags/Rust/",
+                    r#"<a href="/about/#contact">About</a><a href="/gone/">Gone</a>"#
+                )
+                .iter()
+                .map(|b| b.to_string())
+                .collect::<Vec<_>>(),
+            elements_are![eq("tags/Rust: '/gone/' doesn't exist on the site")]
+        );
+
+        fs::remove_dir_all(&outdir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+use std::fmt::Write as _;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use orgize::SyntaxNode;
+use orgize::ast::SourceBlock;
+use orgize::export::{Container, HtmlEscape};
+use orgize::rowan::ast::AstNode;
+
+use crate::highlight;
+use crate::page;
+use crate::tangle;
+
+/// What a source block exports, from its `:exports` header argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exports {
+    Code,
+    Results,
+    /// Default, so pages written before `:exports` was supported keep their results
+    Both,
+    None,
+}
+
+impl Exports {
+    pub fn code(self) -> bool {
+        matches!(self, Exports::Code | Exports::Both)
+    }
+
+    pub fn results(self) -> bool {
+        matches!(self, Exports::Results | Exports::Both)
+    }
+}
+
+/// How a source block is presented, from its switches, header arguments and
+/// affiliated keywords.
+///
+/// - `-n [START]` numbers lines from START, or 1.
+/// - `+n [STEP]` numbers lines continuing the previous numbered block.
+/// - `:hl_lines 1,3-4` emphasises lines, counting from the first one of the block.
+/// - `#+caption:`, or `:file`, or `#+name:` titles the block.
+/// - `:exports code|results|both|none`
+/// - `:tangle yes|PATH`, `:noweb yes` and `:noweb-ref NAME`, see [`crate::tangle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub language: Option<String>,
+    /// First line number, if numbered
+    pub first_line: Option<usize>,
+    pub emphasised: Vec<RangeInclusive<usize>>,
+    /// Inline HTML for the `<figcaption>`
+    pub title: Option<String>,
+    pub exports: Exports,
+    /// File to tangle the block into, relative to the page directory
+    pub tangle: Option<PathBuf>,
+    /// Whether `<<ref>>`s are expanded when tangling
+    pub noweb: bool,
+    /// Name `<<ref>>`s use to include this block, `:noweb-ref` or `#+name:`
+    pub noweb_ref: Option<String>,
+}
+
+impl Options {
+    /// Reads the options of a block on `file_rel_path`. `last_line` is the last
+    /// number used by a numbered block on the page, for `+n`.
+    pub fn new(block: &SourceBlock, last_line: usize, file_rel_path: &Path) -> Self {
+        let parameters = block
+            .parameters()
+            .map(|p| parse_parameters(&p))
+            .unwrap_or_default();
+        let parameter = |key: &str| {
+            parameters
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.as_str())
+        };
+
+        let mut first_line = None;
+        if let Some(switches) = block.switches() {
+            let mut words = switches.split_whitespace().peekable();
+            while let Some(word) = words.next() {
+                let number = words.peek().and_then(|n| n.parse::<usize>().ok());
+                if number.is_some() {
+                    words.next();
+                }
+                match word {
+                    "-n" => first_line = Some(number.unwrap_or(1)),
+                    "+n" => first_line = Some(last_line + number.unwrap_or(1)),
+                    _ => {}
+                }
+            }
+        }
+
+        let exports = match parameter(":exports") {
+            Some("code") => Exports::Code,
+            Some("results") => Exports::Results,
+            Some("none") => Exports::None,
+            Some("both") | None => Exports::Both,
+            Some(other) => {
+                log::warn!("Ignoring unknown ':exports {other}'");
+                Exports::Both
+            }
+        };
+
+        let language = block.language().map(|l| l.to_string());
+        let name = page::affiliated_keyword(block.syntax(), "NAME");
+        let title = page::affiliated_keyword(block.syntax(), "CAPTION")
+            .map(|caption| page::title_html(&caption))
+            .or_else(|| parameter(":file").map(|file| format!("<code>{}</code>", HtmlEscape(file))))
+            .or_else(|| name.as_ref().map(|name| HtmlEscape(name).to_string()));
+
+        Self {
+            first_line,
+            emphasised: parameter(":hl_lines")
+                .map(parse_line_ranges)
+                .unwrap_or_default(),
+            title,
+            exports,
+            tangle: parameter(":tangle")
+                .and_then(|target| tangle::target(target, language.as_deref(), file_rel_path)),
+            noweb: parameter(":noweb").is_some_and(|noweb| noweb != "no"),
+            noweb_ref: parameter(":noweb-ref").map(String::from).or(name),
+            language,
+        }
+    }
+
+    /// Number of the last line of a block with `lines` lines, if numbered
+    pub fn last_line(&self, lines: usize) -> Option<usize> {
+        self.first_line.map(|first| first + lines.saturating_sub(1))
+    }
+}
+
+/// Splits `:key value words :other value` header arguments.
+pub fn parse_parameters(parameters: &str) -> Vec<(String, String)> {
+    let mut parsed: Vec<(String, String)> = vec![];
+    for word in parameters.split_whitespace() {
+        if word.starts_with(':') {
+            parsed.push((word.to_string(), String::new()));
+        } else if let Some((_, value)) = parsed.last_mut() {
+            if !value.is_empty() {
+                value.push(' ');
+            }
+            value.push_str(word.trim_matches('"'));
+        }
+    }
+    parsed
+}
+
+/// Parses `1,3-4` or `"1 3-4"` line ranges.
+fn parse_line_ranges(ranges: &str) -> Vec<RangeInclusive<usize>> {
+    ranges
+        .split([',', ' '])
+        .filter(|r| !r.is_empty())
+        .filter_map(|range| {
+            let parsed = match range.split_once('-') {
+                Some((start, end)) => start.parse().ok().zip(end.parse().ok()),
+                None => range.parse().ok().map(|n| (n, n)),
+            };
+            if parsed.is_none() {
+                log::warn!("Ignoring invalid line range '{range}'");
+            }
+            parsed.map(|(start, end)| start..=end)
+        })
+        .collect()
+}
+
+/// Element holding the `#+RESULTS:` of a source block, if any.
+pub fn results(block: &SourceBlock) -> Option<SyntaxNode> {
+    let mut keyword = false;
+    for node in block
+        .syntax()
+        .siblings(orgize::rowan::Direction::Next)
+        .skip(1)
+    {
+        if let Some(k) = orgize::ast::Keyword::cast(node.clone()) {
+            // Lowercase affiliated keywords are parsed as keywords
+            keyword |= k.key().eq_ignore_ascii_case("RESULTS");
+            continue;
+        }
+        return (keyword || page::affiliated_keyword(&node, "RESULTS").is_some()).then_some(node);
+    }
+    None
+}
+
+/// Syntax node of the elements a source block can output as results
+pub fn results_syntax(container: &Container) -> Option<&SyntaxNode> {
+    match container {
+        Container::FixedWidth(e) => Some(e.syntax()),
+        Container::ExampleBlock(e) => Some(e.syntax()),
+        Container::ExportBlock(e) => Some(e.syntax()),
+        Container::SourceBlock(e) => Some(e.syntax()),
+        Container::Drawer(e) => Some(e.syntax()),
+        Container::Paragraph(e) => Some(e.syntax()),
+        Container::List(e) => Some(e.syntax()),
+        Container::OrgTable(e) => Some(e.syntax()),
+        _ => None,
+    }
+}
+
+/// Renders a source block on `file_rel_path`, highlighted and decorated as per its options.
+pub fn to_html(block: &SourceBlock, options: &Options, file_rel_path: &Path) -> String {
+    let code = block.value();
+    let language = options.language.as_deref();
+
+    let mut html = String::new();
+    if let Some(title) = &options.title {
+        let _ = write!(
+            html,
+            r#"<figure class="src-block"><figcaption>{title}</figcaption>"#
+        );
+    }
+
+    // `hl-code` carries the theme background
+    match language {
+        Some(language) => {
+            let _ = write!(
+                html,
+                r#"<pre class="hl-code"><code class="language-{}">"#,
+                HtmlEscape(language)
+            );
+        }
+        None => html.push_str(r#"<pre class="hl-code"><code>"#),
+    }
+
+    if options.first_line.is_none() && options.emphasised.is_empty() {
+        html.push_str(&highlight::to_html(&code, language));
+    } else {
+        for (i, line) in highlight::lines(&code, language).iter().enumerate() {
+            html.push_str(r#"<span class="line"#);
+            if options.emphasised.iter().any(|r| r.contains(&(i + 1))) {
+                html.push_str(" hl-emphasis");
+            }
+            html.push('"');
+            if let Some(first) = options.first_line {
+                let _ = write!(html, r#" data-line="{}""#, first + i);
+            }
+            let _ = writeln!(html, ">{line}</span>");
+        }
+    }
+
+    html.push_str("</code></pre>");
+    if let Some(target) = &options.tangle {
+        let href = file_rel_path.with_extension("").join(target);
+        let _ = write!(
+            html,
+            r#"<p class="tangle"><a href="/{}" download>{}</a></p>"#,
+            HtmlEscape(href.to_string_lossy()),
+            HtmlEscape(target.to_string_lossy()),
+        );
+    }
+    if options.title.is_some() {
+        html.push_str("</figure>");
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use indoc::indoc;
+    use orgize::Org;
+
+    fn blocks(contents: &str) -> Vec<SourceBlock> {
+        Org::parse(contents)
+            .document()
+            .syntax()
+            .descendants()
+            .filter_map(SourceBlock::cast)
+            .collect()
+    }
+
+    #[gtest]
+    fn options() {
+        let blocks = blocks(indoc! {r###"
+          #+caption: The /main/ function
+          #+begin_src rust -n 10 :exports code :hl_lines 1,3-4
+          #+end_src
+
+          #+name: continued
+          #+begin_src rust +n :file main.rs :results output replace :tangle src/main.rs
+          #+end_src
+
+          #+NAME: named
+          #+begin_src rust :exports results
+          #+end_src
+        "###});
+
+        expect_that!(
+            Options::new(&blocks[0], 0, Path::new("b/post.org")),
+            eq(&Options {
+                language: Some(String::from("rust")),
+                first_line: Some(10),
+                emphasised: vec![1..=1, 3..=4],
+                title: Some(String::from("The <i>main</i> function")),
+                exports: Exports::Code,
+                tangle: None,
+                noweb: false,
+                noweb_ref: None,
+            })
+        );
+
+        let continued = Options::new(&blocks[1], 12, Path::new("b/post.org"));
+        expect_that!(continued.first_line, some(eq(13)));
+        expect_that!(continued.title, some(eq("<code>main.rs</code>")));
+        expect_that!(continued.exports, eq(Exports::Both));
+        expect_that!(continued.tangle, some(eq(Path::new("src/main.rs"))));
+        expect_that!(continued.noweb_ref, some(eq("continued")));
+        expect_that!(
+            to_html(&blocks[1], &continued, Path::new("b/post.org")),
+            contains_substring(
+                r#"<p class="tangle"><a href="/b/post/src/main.rs" download>src/main.rs</a></p></figure>"#
+            )
+        );
+
+        let named = Options::new(&blocks[2], 0, Path::new("b/post.org"));
+        expect_that!(named.first_line, none());
+        expect_that!(named.title, some(eq("named")));
+        expect_that!(named.exports.code(), eq(false));
+    }
+
+    #[gtest]
+    fn numbered_and_emphasised_lines() {
+        let blocks = blocks(indoc! {r###"
+          #+begin_src text -n 7 :hl_lines 2
+          one
+          <two>
+          #+end_src
+        "###});
+        let options = Options::new(&blocks[0], 0, Path::new("b/post.org"));
+
+        expect_that!(
+            to_html(&blocks[0], &options, Path::new("b/post.org")),
+            eq(indoc! {r###"
+              <pre class="hl-code"><code class="language-text"><span class="line" data-line="7">one</span>
+              <span class="line hl-emphasis" data-line="8">&lt;two&gt;</span>
+              </code></pre>"###})
+        );
+        expect_that!(options.last_line(2), some(eq(8)));
+    }
+
+    #[gtest]
+    fn finds_results() {
+        let blocks = blocks(indoc! {r###"
+          #+begin_src python
+          print(1)
+          #+end_src
+
+          #+RESULTS:
+          : 1
+
+          #+begin_src python
+          print(2)
+          #+end_src
+
+          Not a result
+        "###});
+
+        expect_that!(
+            results(&blocks[0]).map(|n| n.to_string()),
+            some(eq("#+RESULTS:\n: 1\n\n"))
+        );
+        expect_that!(results(&blocks[1]), none());
+    }
+}
@@ -1,9 +1,165 @@
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use quick_xml::escape::escape;
+use regex::Regex;
+
+use crate::page;
+use crate::tags;
+use serde::Serialize;
 
-// TODO: Generate ~/Notes/Personal/Blog/sitemap.xml
 // TODO: Move feed generation to here
 
+/// A page announced on the feeds.
+///
+/// Kept format-agnostic so every feed is rendered from the same data.
+#[derive(Debug, Clone)]
+struct FeedEntry {
+    /// Permanent identifier, see [Sitemap::entry_id]
+    id: String,
+    title: Option<String>,
+    /// `author` property or `#+author:` keyword
+    author: Option<String>,
+    description: Option<String>,
+    link: String,
+    /// `publication_date` property
+    published: Option<chrono::DateTime<chrono::FixedOffset>>,
+    /// `modified_time` property
+    updated: Option<chrono::DateTime<chrono::FixedOffset>>,
+    tags: Vec<String>,
+    /// Rendered page, as produced by [crate::page::to_html] and cleaned up by [feed_html]
+    content_html: String,
+}
+
+/// JSON Feed document.
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: String,
+    feed_url: String,
+    description: &'a str,
+    language: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<JsonFeedAuthor<'a>>,
+    items: Vec<JsonFeedItem<'a>>,
+}
+
+/// JSON Feed author.
+#[derive(Serialize)]
+struct JsonFeedAuthor<'a> {
+    name: &'a str,
+}
+
+/// JSON Feed item.
+#[derive(Serialize)]
+struct JsonFeedItem<'a> {
+    id: String,
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<JsonFeedAuthor<'a>>,
+    content_html: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_modified: Option<String>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+}
+
+/// Adapts a page fragment for feed readers.
+///
+/// - Relative `href`s, `src`s and `srcset` URLs are resolved against the page
+///   URL, as readers don't know where the content came from.
+/// - HTMX attributes are dropped, there's no HTMX on feed readers.
+fn feed_html(html: &str, page_url: &str) -> String {
+    static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[a-zA-Z][^>]*>").unwrap());
+    static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"\s+([^\s=/>]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'=<>`]+)))?"#).unwrap()
+    });
+
+    let base = url::Url::parse(page_url).ok();
+
+    TAG.replace_all(html, |tag: &regex::Captures| {
+        let tag = &tag[0];
+        let name_end = tag[1..]
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .map_or(tag.len(), |i| i + 1);
+        let (name, rest) = tag.split_at(name_end);
+
+        let mut out = String::from(name);
+        let mut attributes_end = 0;
+        for attribute in ATTRIBUTE.captures_iter(rest) {
+            attributes_end = attribute.get(0).unwrap().end();
+
+            let key = &attribute[1];
+            if key == "preload" || key.starts_with("hx-") {
+                continue;
+            }
+            let value = attribute
+                .get(2)
+                .or(attribute.get(3))
+                .or(attribute.get(4))
+                .map(|v| v.as_str());
+
+            match (key, value, &base) {
+                ("href" | "src", Some(value), Some(base)) => {
+                    let value = page::unescape(value);
+                    let value = base
+                        .join(&value)
+                        .map_or_else(|_| value.to_string(), String::from);
+                    let _ = write!(out, r#" {key}="{}""#, escape(&value));
+                }
+                ("srcset", Some(value), Some(base)) => {
+                    let value = page::unescape(value);
+                    let value = value
+                        .split(',')
+                        .map(|candidate| {
+                            let candidate = candidate.trim();
+                            let (url, descriptor) =
+                                candidate.split_once(' ').unwrap_or((candidate, ""));
+                            let url = base
+                                .join(url)
+                                .map_or_else(|_| url.to_string(), String::from);
+                            format!("{url} {descriptor}").trim_end().to_string()
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let _ = write!(out, r#" {key}="{}""#, escape(&value));
+                }
+                (key, Some(value), _) => {
+                    // Single quoted and unquoted values may have `"`
+                    let value = page::unescape(value);
+                    let _ = write!(out, r#" {key}="{}""#, escape(value));
+                }
+                (key, None, _) => {
+                    let _ = write!(out, " {key}");
+                }
+            }
+        }
+        out.push_str(&rest[attributes_end..]);
+        out
+    })
+    .into_owned()
+}
+
+/// A `<url>` entry on sitemap.xml.
+///
+/// See https://www.sitemaps.org/protocol.html
+#[derive(Debug, Clone)]
+struct SitemapEntry {
+    loc: String,
+    lastmod: Option<chrono::DateTime<chrono::FixedOffset>>,
+}
+
 pub struct Sitemap {
     root_address: String,
 
@@ -13,7 +169,17 @@ pub struct Sitemap {
 
     generation_time: chrono::DateTime<chrono::Local>,
 
-    rss_entries: Vec<rss::Item>,
+    /// Embed the rendered pages on the RSS and Atom feeds
+    full_content: bool,
+    /// Maximum number of entries on each feed
+    max_entries: Option<usize>,
+    /// Default author for pages without one
+    author: Option<String>,
+
+    feed_entries: Vec<FeedEntry>,
+    sitemap_entries: Vec<SitemapEntry>,
+    /// Tags of every page but drafts, with feed entries or not
+    tags: BTreeSet<String>,
 }
 
 impl Sitemap {
@@ -30,80 +196,419 @@ impl Sitemap {
             description,
             language,
             generation_time,
-            rss_entries: vec![],
+            full_content: false,
+            max_entries: None,
+            author: None,
+            feed_entries: vec![],
+            sitemap_entries: vec![],
+            tags: BTreeSet::new(),
         }
     }
 
-    pub fn push(&mut self, doc: &orgize::Org, out_path: &Path) {
-        println!("FIXME: actually push data into the sitemap");
-        log::debug!("Generating RSS entry for '{}'...", out_path.display());
+    /// Embeds the rendered pages on the RSS and Atom feeds instead of just linking to them.
+    pub fn full_content(mut self, full_content: bool) -> Self {
+        self.full_content = full_content;
+        self
+    }
+
+    /// Keeps only the newest `max_entries` entries on each feed.
+    pub fn max_entries(mut self, max_entries: Option<usize>) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Sets the site author, used for pages that don't name one.
+    pub fn author(mut self, author: Option<String>) -> Self {
+        self.author = author;
+        self
+    }
+
+    /// Registers a page on the feeds.
+    ///
+    /// - Drafts are kept out of everything.
+    /// - `skip_feed` pages are still listed on the sitemap, they just don't get announced.
+    /// - `skip_sitemap` pages are kept out of the sitemap.
+    pub fn push(&mut self, doc: &orgize::Org, tags: &[String], out_path: &Path, html: &str) {
+        let mut skip_feed = false;
+        let mut skip_sitemap = false;
 
         if let Some(properties) = doc.document().properties() {
-            if properties.get("skip_feed").is_some() {
-                return;
-            }
             if properties.get("Draft").is_some() {
+                log::debug!("Skipping feeds for draft '{}'", out_path.display());
                 return;
             }
+            skip_feed = properties.get("skip_feed").is_some();
+            skip_sitemap = properties.get("skip_sitemap").is_some();
         }
+        self.tags.extend(tags.iter().cloned());
 
-        self.push_rss(doc, out_path);
-        self.push_sitemap(doc, out_path);
+        if !skip_feed {
+            self.push_feed(doc, tags, out_path, html);
+        }
+        if !skip_sitemap {
+            self.push_sitemap(doc, out_path);
+        }
     }
 
-    fn push_rss(&mut self, doc: &orgize::Org, out_path: &Path) {
-        log::debug!("Generating RSS entry for '{}'...", out_path.display());
+    /// Lists another page generated for `doc`, like the next pages of its
+    /// archive, on the sitemap. `site_path` is like `/b/page/2`.
+    pub fn push_subpage(&mut self, doc: &orgize::Org, site_path: &str) {
+        if let Some(properties) = doc.document().properties()
+            && (properties.get("Draft").is_some() || properties.get("skip_sitemap").is_some())
+        {
+            return;
+        }
+        self.sitemap_entries.push(SitemapEntry {
+            loc: format!(
+                "{}/{}/",
+                self.root_address.trim_end_matches('/'),
+                site_path.trim_matches('/')
+            ),
+            lastmod: None,
+        });
+    }
 
-        let mut item = rss::ItemBuilder::default();
+    /// Canonical URL of a page, `$ROOT/path/to/page/`
+    fn page_url(&self, out_path: &Path) -> String {
+        let mut path = out_path.to_path_buf();
+        path.set_extension("");
+        format!(
+            "{}/{}/",
+            self.root_address.trim_end_matches('/'),
+            path.display()
+        )
+    }
 
-        if let Some(properties) = doc.document().properties() {
+    fn push_feed(&mut self, doc: &orgize::Org, tags: &[String], out_path: &Path, html: &str) {
+        log::debug!("Generating feed entry for '{}'...", out_path.display());
+
+        let properties = doc.document().properties();
+        let link = self.page_url(out_path);
+        let mut entry = FeedEntry {
+            id: self.entry_id(
+                properties.as_ref().and_then(|p| p.get("ID")).as_deref(),
+                out_path,
+            ),
+            title: doc.title(),
+            author: doc
+                .document()
+                .keywords()
+                .find(|kw| kw.key().eq_ignore_ascii_case("AUTHOR"))
+                .map(|kw| kw.value().trim().to_string())
+                .or_else(|| self.author.clone()),
+            description: None,
+            content_html: feed_html(html, &link),
+            link,
+            published: page::org_date(doc, "publication_date", out_path),
+            updated: page::org_date(doc, "modified_time", out_path),
+            tags: tags.to_vec(),
+        };
+
+        if let Some(properties) = properties {
+            if let Some(author) = properties.get("author") {
+                entry.author = Some(author.to_string());
+            }
             if let Some(title) = properties.get("title") {
-                item.title(title.to_string());
+                entry.title = Some(title.to_string());
             }
             if let Some(description) = properties.get("description") {
-                item.description(description.to_string());
+                entry.description = Some(description.to_string());
             }
-            if let Some(publication_date) = properties.get("publication_date") {
-                item.pub_date(publication_date.to_string());
-            }
-            let mut path = out_path.to_path_buf();
-            path.set_extension("");
-            item.link(Some(format!("{}/{}", self.root_address, path.display())));
         }
 
-        self.rss_entries.push(item.build());
+        self.feed_entries.push(entry);
+    }
+
+    fn push_sitemap(&mut self, doc: &orgize::Org, out_path: &Path) {
+        log::debug!("Generating Sitemap entry for '{}'...", out_path.display());
+
+        self.sitemap_entries.push(SitemapEntry {
+            loc: self.page_url(out_path),
+            lastmod: page::org_date(doc, "modified_time", out_path)
+                .or_else(|| page::org_date(doc, "created_time", out_path)),
+        });
     }
-    fn push_sitemap(&mut self, _doc: &orgize::Org, out_dir: &Path) {
-        log::debug!("Generating Sitemap entry for '{}'...", out_dir.display());
+
+    /// Feed entries, newest first and capped to `max_entries`.
+    ///
+    /// Undated entries go last, in the order they were pushed.
+    fn entries(&self) -> impl Iterator<Item = &FeedEntry> {
+        self.tagged_entries(None)
+    }
+
+    /// Like [Sitemap::entries], but only for entries with the given tag.
+    fn tagged_entries(&self, tag: Option<&str>) -> impl Iterator<Item = &FeedEntry> {
+        let mut entries: Vec<&FeedEntry> = self
+            .feed_entries
+            .iter()
+            .filter(|e| tag.is_none_or(|tag| e.tags.iter().any(|t| t == tag)))
+            .collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.published.or(e.updated)));
+        entries
+            .into_iter()
+            .take(self.max_entries.unwrap_or(usize::MAX))
     }
 
-    pub fn generate(&self, out_dir: &Path) {
-        self.generate_rss(out_dir);
-        self.generate_sitemap(out_dir);
+    pub fn generate(&self, out_dir: &Path) -> io::Result<()> {
+        self.generate_rss(out_dir)?;
+        self.generate_tag_rss(out_dir)?;
+        self.generate_atom(out_dir)?;
+        self.generate_json(out_dir)?;
+        self.generate_sitemap(out_dir)?;
+        Ok(())
     }
 
-    fn generate_rss(&self, out_dir: &Path) {
+    fn generate_rss(&self, out_dir: &Path) -> io::Result<()> {
         log::debug!("Generating RSS feed on '{}'...", out_dir.display());
 
+        let channel = self.rss_channel(None);
+
+        let mut rss_out_path: PathBuf = out_dir.to_path_buf();
+        rss_out_path.push("feed.rss");
+        log::info!("Will write RSS feed to '{}'", rss_out_path.display());
+        std::fs::write(rss_out_path, channel.to_string())
+    }
+
+    /// Writes an RSS feed per tag, on `tags/TAG/feed.rss`.
+    ///
+    /// Tags only on `skip_feed` pages get an empty one, as their tag page
+    /// links to it too.
+    fn generate_tag_rss(&self, out_dir: &Path) -> io::Result<()> {
+        for tag in &self.tags {
+            let mut rss_out_path: PathBuf = out_dir.to_path_buf();
+            rss_out_path.push(tags::TAGS_DIR);
+            rss_out_path.push(tag);
+            std::fs::create_dir_all(&rss_out_path)?;
+            rss_out_path.push("feed.rss");
+
+            log::info!("Will write RSS feed to '{}'", rss_out_path.display());
+            std::fs::write(rss_out_path, self.rss_channel(Some(tag)).to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Builds the RSS channel for the whole site, or just for a tag.
+    fn rss_channel(&self, tag: Option<&str>) -> rss::Channel {
+        let (title, link) = match tag {
+            None => (self.title.clone(), self.root_address.clone()),
+            Some(tag) => (
+                format!("{}: {tag}", self.title),
+                format!(
+                    "{}{}/",
+                    self.root_address.trim_end_matches('/'),
+                    tags::tag_path(tag)
+                ),
+            ),
+        };
+
         let channel: rss::Channel = rss::ChannelBuilder::default()
-            .title(self.title.clone())
-            .link(self.root_address.clone())
+            .title(title)
+            .link(link)
             .description(self.description.clone())
             .last_build_date(Some(self.generation_time.to_rfc2822()))
             .language(self.language.clone())
-            .items(self.rss_entries.clone())
+            .items(
+                self.tagged_entries(tag)
+                    .map(|e| self.rss_item(e))
+                    .collect::<Vec<_>>(),
+            )
             .build();
 
         use rss::validation::Validate;
         channel.validate().unwrap();
 
-        let mut rss_out_path: PathBuf = out_dir.to_path_buf();
-        rss_out_path.push("feed.rss");
-        log::info!("Will write RSS feed to '{}'", rss_out_path.display());
-        std::fs::write(rss_out_path, channel.to_string()).unwrap();
+        channel
+    }
+
+    fn rss_item(&self, entry: &FeedEntry) -> rss::Item {
+        let mut item = rss::ItemBuilder::default();
+        item.title(entry.title.clone());
+        item.description(entry.description.clone());
+        item.pub_date(entry.published.map(|d| d.to_rfc2822()));
+        item.link(Some(entry.link.clone()));
+        item.guid(Some(
+            rss::GuidBuilder::default()
+                .value(entry.id.clone())
+                .permalink(false)
+                .build(),
+        ));
+        item.author(entry.author.clone());
+        item.categories(
+            entry
+                .tags
+                .iter()
+                .map(|t| rss::CategoryBuilder::default().name(t.clone()).build())
+                .collect::<Vec<_>>(),
+        );
+        if self.full_content {
+            item.content(Some(entry.content_html.clone()));
+        }
+        item.build()
+    }
+
+    fn generate_atom(&self, out_dir: &Path) -> io::Result<()> {
+        log::debug!("Generating Atom feed on '{}'...", out_dir.display());
+
+        let mut atom_out_path: PathBuf = out_dir.to_path_buf();
+        atom_out_path.push("feed.atom");
+        log::info!("Will write Atom feed to '{}'", atom_out_path.display());
+        std::fs::write(atom_out_path, self.atom_feed().to_string())
+    }
+
+    /// Builds the Atom 1.0 feed.
+    ///
+    /// See https://www.rfc-editor.org/rfc/rfc4287
+    fn atom_feed(&self) -> atom_syndication::Feed {
+        use atom_syndication::{FeedBuilder, LinkBuilder, PersonBuilder};
+
+        let root = format!("{}/", self.root_address.trim_end_matches('/'));
+
+        FeedBuilder::default()
+            .title(self.title.clone())
+            .subtitle(Some(self.description.clone().into()))
+            .id(root.clone())
+            .updated(self.generation_time.fixed_offset())
+            .base(Some(root.clone()))
+            .lang(Some(self.language.clone()))
+            .authors(
+                self.author
+                    .iter()
+                    .map(|a| PersonBuilder::default().name(a.clone()).build())
+                    .collect::<Vec<_>>(),
+            )
+            .links(vec![
+                LinkBuilder::default()
+                    .href(format!("{root}feed.atom"))
+                    .rel("self")
+                    .mime_type(Some(String::from("application/atom+xml")))
+                    .build(),
+                LinkBuilder::default()
+                    .href(root)
+                    .rel("alternate")
+                    .mime_type(Some(String::from("text/html")))
+                    .build(),
+            ])
+            .entries(
+                self.entries()
+                    .map(|e| self.atom_entry(e))
+                    .collect::<Vec<_>>(),
+            )
+            .build()
     }
 
-    fn generate_sitemap(&self, out_dir: &Path) {
+    /// Permanent identifier for an entry.
+    ///
+    /// Uses the page `ID` when available so the entry survives moves and renames,
+    /// falling back to a hash of the source path.
+    fn entry_id(&self, id: Option<&str>, out_path: &Path) -> String {
+        match id.map(|id| (id, uuid::Uuid::try_parse(id))) {
+            Some((_, Ok(uuid))) => uuid.urn().to_string(),
+            Some((id, Err(_))) => format!("{}/#{id}", self.root_address.trim_end_matches('/')),
+            None => uuid::Uuid::new_v5(
+                &uuid::Uuid::NAMESPACE_URL,
+                format!("file:{}", out_path.display()).as_bytes(),
+            )
+            .urn()
+            .to_string(),
+        }
+    }
+
+    fn atom_entry(&self, entry: &FeedEntry) -> atom_syndication::Entry {
+        use atom_syndication::{
+            CategoryBuilder, ContentBuilder, EntryBuilder, LinkBuilder, PersonBuilder,
+        };
+
+        let published = entry.published;
+        let updated = entry
+            .updated
+            .or(published)
+            .unwrap_or_else(|| self.generation_time.fixed_offset());
+
+        EntryBuilder::default()
+            .title(entry.title.clone().unwrap_or_default())
+            .id(entry.id.clone())
+            .authors(
+                entry
+                    .author
+                    .iter()
+                    .map(|a| PersonBuilder::default().name(a.clone()).build())
+                    .collect::<Vec<_>>(),
+            )
+            .updated(updated)
+            .published(published)
+            .summary(entry.description.clone().map(|d| d.into()))
+            .content(self.full_content.then(|| {
+                ContentBuilder::default()
+                    .value(Some(entry.content_html.clone()))
+                    .content_type(Some(String::from("html")))
+                    .build()
+            }))
+            .links(vec![
+                LinkBuilder::default()
+                    .href(entry.link.clone())
+                    .rel("alternate")
+                    .mime_type(Some(String::from("text/html")))
+                    .build(),
+            ])
+            .categories(
+                entry
+                    .tags
+                    .iter()
+                    .map(|t| CategoryBuilder::default().term(t.clone()).build())
+                    .collect::<Vec<_>>(),
+            )
+            .build()
+    }
+
+    fn generate_json(&self, out_dir: &Path) -> io::Result<()> {
+        log::debug!("Generating JSON feed on '{}'...", out_dir.display());
+
+        let mut json_out_path: PathBuf = out_dir.to_path_buf();
+        json_out_path.push("feed.json");
+        log::info!("Will write JSON feed to '{}'", json_out_path.display());
+        std::fs::write(json_out_path, serde_json::to_string(&self.json_feed())?)
+    }
+
+    /// Builds the JSON Feed 1.1 feed.
+    ///
+    /// See https://www.jsonfeed.org/version/1.1/
+    fn json_feed(&self) -> JsonFeed<'_> {
+        let root = format!("{}/", self.root_address.trim_end_matches('/'));
+
+        JsonFeed {
+            version: "https://jsonfeed.org/version/1.1",
+            title: &self.title,
+            home_page_url: root.clone(),
+            feed_url: format!("{root}feed.json"),
+            description: &self.description,
+            language: &self.language,
+            authors: self
+                .author
+                .iter()
+                .map(|name| JsonFeedAuthor { name })
+                .collect(),
+            items: self
+                .entries()
+                .map(|entry| JsonFeedItem {
+                    id: entry.id.clone(),
+                    url: &entry.link,
+                    title: entry.title.as_deref(),
+                    summary: entry.description.as_deref(),
+                    authors: entry
+                        .author
+                        .iter()
+                        .map(|name| JsonFeedAuthor { name })
+                        .collect(),
+                    content_html: &entry.content_html,
+                    date_published: entry.published.map(|d| d.to_rfc3339()),
+                    date_modified: entry.updated.map(|d| d.to_rfc3339()),
+                    tags: &entry.tags,
+                })
+                .collect(),
+        }
+    }
+
+    fn generate_sitemap(&self, out_dir: &Path) -> io::Result<()> {
         log::debug!("Generating Sitemap feed on '{}'...", out_dir.display());
 
         let mut sitemap_out_path: PathBuf = out_dir.to_path_buf();
@@ -113,14 +618,327 @@ impl Sitemap {
             "Will write Sitemap feed to '{}'",
             sitemap_out_path.display()
         );
-        // fs::write(sitemap_out_path, channel.to_string())?;
+        std::fs::write(sitemap_out_path, self.sitemap_xml())
+    }
+
+    /// Renders sitemap.xml
+    ///
+    /// The site root goes first as it's not an org page.
+    fn sitemap_xml(&self) -> String {
+        let mut xml = String::from(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n",
+            r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#,
+            "\n",
+        ));
+
+        let root = SitemapEntry {
+            loc: format!("{}/", self.root_address.trim_end_matches('/')),
+            lastmod: None,
+        };
+        for entry in std::iter::once(&root).chain(self.sitemap_entries.iter()) {
+            xml.push_str("  <url>\n");
+            let _ = writeln!(xml, "    <loc>{}</loc>", escape(&entry.loc));
+            if let Some(lastmod) = entry.lastmod {
+                let _ = writeln!(xml, "    <lastmod>{}</lastmod>", lastmod.to_rfc3339());
+            }
+            xml.push_str("  </url>\n");
+        }
+
+        xml.push_str("</urlset>\n");
+        xml
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use indoc::indoc;
+
+    fn test_sitemap() -> Sitemap {
+        Sitemap::new(
+            String::from("https://example.com/"),
+            String::from("TITLE"),
+            String::from("DESCRIPTION"),
+            String::from("en-GB"),
+            chrono::Local::now(),
+        )
+    }
+
+    #[gtest]
+    fn sitemap_lists_pages() {
+        let mut sitemap = test_sitemap();
+
+        let post = orgize::Org::parse(indoc! {r###"
+          :PROPERTIES:
+          :created_time: 2025-01-22T16:00:00-03:00
+          :modified_time: 2025-01-23T16:00:00-03:00
+          :END:
+          #+title: Post
+        "###});
+        sitemap.push(&post, &[], Path::new("b/post.org"), "");
+
+        let listing = orgize::Org::parse(indoc! {r###"
+          :PROPERTIES:
+          :created_time: 2025-01-17T16:00:00-03:00
+          :skip_feed: True
+          :END:
+        "###});
+        sitemap.push(&listing, &[], Path::new("b.org"), "");
+        sitemap.push_subpage(&listing, "/b/page/2");
+
+        let draft = orgize::Org::parse(indoc! {r###"
+          :PROPERTIES:
+          :Draft: True
+          :END:
+        "###});
+        sitemap.push(&draft, &[], Path::new("b/draft.org"), "");
+
+        expect_that!(sitemap.feed_entries.len(), eq(1));
+        expect_that!(
+            sitemap.sitemap_xml(),
+            eq(indoc! {r###"
+              <?xml version="1.0" encoding="UTF-8"?>
+              <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <url>
+                  <loc>https://example.com/</loc>
+                </url>
+                <url>
+                  <loc>https://example.com/b/post/</loc>
+                  <lastmod>2025-01-23T16:00:00-03:00</lastmod>
+                </url>
+                <url>
+                  <loc>https://example.com/b/</loc>
+                  <lastmod>2025-01-17T16:00:00-03:00</lastmod>
+                </url>
+                <url>
+                  <loc>https://example.com/b/page/2/</loc>
+                </url>
+              </urlset>
+            "###})
+        );
+    }
+
+    #[gtest]
+    fn atom_entries() {
+        let mut sitemap = test_sitemap();
+
+        let post = orgize::Org::parse(indoc! {r###"
+          :PROPERTIES:
+          :ID: 0F3C1B8E-6A4B-4C0E-9A57-2E1D5B7C9F10
+          :publication_date: Wed, 22 Jan 2025 15:05 PDT
+          :modified_time: 2025-01-23T16:00:00-03:00
+          :END:
+          #+title: Post
+        "###});
+        sitemap.push(&post, &[String::from("Rust")], Path::new("b/post.org"), "");
+
+        let feed = sitemap.atom_feed();
+        expect_that!(feed.base(), some(eq("https://example.com/")));
+
+        let entry = &feed.entries()[0];
+        expect_that!(
+            entry.id(),
+            eq("urn:uuid:0f3c1b8e-6a4b-4c0e-9a57-2e1d5b7c9f10")
+        );
+        expect_that!(entry.title().as_str(), eq("Post"));
+        expect_that!(
+            entry.updated().to_rfc3339(),
+            eq("2025-01-23T16:00:00-03:00")
+        );
+        expect_that!(
+            entry.published().map(|d| d.to_rfc3339()),
+            some(eq("2025-01-22T15:05:00-07:00"))
+        );
+        expect_that!(entry.categories()[0].term(), eq("Rust"));
+        expect_that!(entry.links()[0].href(), eq("https://example.com/b/post/"));
+    }
+
+    #[gtest]
+    fn json_feed_items() {
+        let mut sitemap = test_sitemap();
 
-//     #[test]
-//     fn it_works() {}
-// }
+        let post = orgize::Org::parse(indoc! {r###"
+          :PROPERTIES:
+          :publication_date: Wed, 22 Jan 2025 15:05 PDT
+          :END:
+          #+title: Post
+        "###});
+        sitemap.push(
+            &post,
+            &[String::from("Rust")],
+            Path::new("b/post.org"),
+            "<h1>Post</h1>",
+        );
+
+        let json = serde_json::to_value(sitemap.json_feed()).unwrap();
+        expect_that!(
+            json["version"].as_str(),
+            some(eq("https://jsonfeed.org/version/1.1"))
+        );
+        expect_that!(
+            json["feed_url"].as_str(),
+            some(eq("https://example.com/feed.json"))
+        );
+
+        let item = &json["items"][0];
+        expect_that!(
+            item["id"].as_str(),
+            some(eq("urn:uuid:30a6d670-01c2-5a92-aa68-c5776c88a1cb"))
+        );
+        expect_that!(item["content_html"].as_str(), some(eq("<h1>Post</h1>")));
+        expect_that!(
+            item["date_published"].as_str(),
+            some(eq("2025-01-22T15:05:00-07:00"))
+        );
+        expect_that!(item["tags"][0].as_str(), some(eq("Rust")));
+        expect_that!(item.get("date_modified"), none());
+    }
+
+    #[gtest]
+    fn rss_guid_categories_and_author() {
+        let mut sitemap = test_sitemap().author(Some(String::from("site@example.com (Site)")));
+
+        let post = orgize::Org::parse(indoc! {r###"
+          :PROPERTIES:
+          :ID: 0F3C1B8E-6A4B-4C0E-9A57-2E1D5B7C9F10
+          :END:
+          #+title: Post
+          #+author: me@example.com (Me)
+        "###});
+        sitemap.push(
+            &post,
+            &[String::from("Rust"), String::from("Org")],
+            Path::new("b/post.org"),
+            "",
+        );
+        let anonymous = orgize::Org::parse("#+title: Anonymous\n");
+        sitemap.push(&anonymous, &[], Path::new("b/anonymous.org"), "");
+
+        let item = sitemap.rss_item(&sitemap.feed_entries[0]);
+        let guid = item.guid().unwrap();
+        expect_that!(
+            guid.value(),
+            eq("urn:uuid:0f3c1b8e-6a4b-4c0e-9a57-2e1d5b7c9f10")
+        );
+        expect_that!(guid.is_permalink(), eq(false));
+        expect_that!(
+            item.categories()
+                .iter()
+                .map(|c| c.name())
+                .collect::<Vec<_>>(),
+            eq(&vec!["Rust", "Org"])
+        );
+        expect_that!(item.author(), some(eq("me@example.com (Me)")));
+
+        let item = sitemap.rss_item(&sitemap.feed_entries[1]);
+        expect_that!(item.author(), some(eq("site@example.com (Site)")));
+        expect_that!(item.guid().unwrap().value(), starts_with("urn:uuid:"));
+    }
+
+    #[gtest]
+    fn entries_are_sorted_and_capped() {
+        let mut sitemap = test_sitemap().max_entries(Some(2));
+
+        for (path, date) in [
+            ("b/old.org", "2024-01-01"),
+            ("b/undated.org", "Someday"),
+            ("b/new.org", "<2025-03-01 Sat>"),
+            ("b/mid.org", "Wed, 22 Jan 2025 15:05 PDT"),
+        ] {
+            let post =
+                orgize::Org::parse(format!(":PROPERTIES:\n:publication_date: {date}\n:END:\n"));
+            sitemap.push(&post, &[], Path::new(path), "");
+        }
+
+        expect_that!(
+            sitemap
+                .entries()
+                .map(|e| e.link.as_str())
+                .collect::<Vec<_>>(),
+            eq(&vec![
+                "https://example.com/b/new/",
+                "https://example.com/b/mid/"
+            ])
+        );
+
+        let item = sitemap.rss_item(sitemap.entries().nth(1).unwrap());
+        expect_that!(item.pub_date(), some(eq("Wed, 22 Jan 2025 15:05:00 -0700")));
+    }
+
+    #[gtest]
+    fn tag_feeds() {
+        let mut sitemap = test_sitemap();
+
+        for (path, tags) in [
+            ("b/rust.org", vec![String::from("Rust")]),
+            ("b/org.org", vec![String::from("Org")]),
+            (
+                "b/both.org",
+                vec![String::from("Org"), String::from("Rust")],
+            ),
+        ] {
+            let post = orgize::Org::parse("#+title: Post\n");
+            sitemap.push(&post, &tags, Path::new(path), "");
+        }
+        let unlisted = orgize::Org::parse(":PROPERTIES:\n:skip_feed: t\n:END:\n");
+        sitemap.push(
+            &unlisted,
+            &[String::from("Quiet")],
+            Path::new("b/quiet.org"),
+            "",
+        );
+
+        let channel = sitemap.rss_channel(Some("Rust"));
+        expect_that!(channel.title(), eq("TITLE: Rust"));
+        expect_that!(channel.link(), eq("https://example.com/tags/Rust/"));
+        expect_that!(
+            channel
+                .items()
+                .iter()
+                .filter_map(|i| i.link())
+                .collect::<Vec<_>>(),
+            eq(&vec![
+                "https://example.com/b/rust/",
+                "https://example.com/b/both/"
+            ])
+        );
+
+        let outdir = std::env::temp_dir().join(format!("blorg-tag-feeds-{}", std::process::id()));
+        sitemap.generate_tag_rss(&outdir).unwrap();
+        let quiet = std::fs::read_to_string(outdir.join("tags/Quiet/feed.rss"));
+        std::fs::remove_dir_all(&outdir).unwrap();
+        expect_that!(
+            quiet,
+            ok(all![
+                contains_substring("TITLE: Quiet"),
+                not(contains_substring("<item>"))
+            ])
+        );
+    }
+
+    #[gtest]
+    fn feed_html_is_absolute_and_htmx_free() {
+        let html = indoc! {r###"
+          <a hx-get="part_2/_.html"
+            preload
+            hx-target="#content"
+            hx-push-url="part_2/"
+            hx-history-target="part_2/"
+            aria-controls="content"
+            href="part_2" />Part 2</a>
+          <img src="../i/32.png"><a href="#why">Why?</a><a href="https://example.org/?a=1&amp;b=2" preload target="_blank" />
+          <abbr title='Say "hi"' class=x>Hi</abbr>
+          <img srcset="/i/a-480w.avif 480w, /i/a.avif">"###};
+
+        expect_that!(
+            feed_html(html, "https://example.com/b/post/"),
+            eq(indoc! {r###"
+              <a aria-controls="content" href="https://example.com/b/post/part_2" />Part 2</a>
+              <img src="https://example.com/b/i/32.png"><a href="https://example.com/b/post/#why">Why?</a><a href="https://example.org/?a=1&amp;b=2" target="_blank" />
+              <abbr title="Say &quot;hi&quot;" class="x">Hi</abbr>
+              <img srcset="https://example.com/i/a-480w.avif 480w, https://example.com/i/a.avif">"###})
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use orgize::ast::{Link, Paragraph};
+use orgize::export::HtmlEscape;
+use orgize::rowan::ast::AstNode;
+use orgize::{Org, SyntaxElement, SyntaxKind, SyntaxNode};
+
+use crate::code;
+use crate::images;
+use crate::links::{self, Target};
+use crate::page;
+use crate::site::Site;
+
+/// Attributes written from the link itself, `#+ATTR_HTML:` can't set them
+const RESERVED_ATTRIBUTES: &[&str] = &["src", "srcset"];
+
+/// Image link a paragraph is made of, alone, so it can be rendered as a figure.
+pub fn standalone_image(paragraph: &Paragraph) -> Option<Link> {
+    let mut elements = paragraph
+        .syntax()
+        .children_with_tokens()
+        .filter(|e| e.kind() != SyntaxKind::AFFILIATED_KEYWORD)
+        .filter(|e| !(e.as_token().is_some() && e.to_string().trim().is_empty()));
+    let link = match elements.next()? {
+        SyntaxElement::Node(node) => Link::cast(node)?,
+        SyntaxElement::Token(_) => return None,
+    };
+    (elements.next().is_none() && link.is_image()).then_some(link)
+}
+
+/// Renders a paragraph with just an image as a `<figure>`, captioned by its
+/// `#+CAPTION:` and identified by its `#+NAME:`.
+pub fn to_html(paragraph: &Paragraph, link: &Link, site: &Site, file_rel_path: &Path) -> String {
+    let node = paragraph.syntax();
+    let mut html = String::from("<figure");
+    if let Some(name) = page::affiliated_keyword(node, "NAME") {
+        let _ = write!(html, r#" id="{}""#, HtmlEscape(name));
+    }
+    html.push('>');
+    html.push_str(&img_html(link, Some(node), site, file_rel_path));
+    if let Some(caption) = page::affiliated_keyword(node, "CAPTION") {
+        let _ = write!(
+            html,
+            "<figcaption>{}</figcaption>",
+            page::title_html(&caption)
+        );
+    }
+    html.push_str("</figure>");
+    html
+}
+
+/// Renders an image link as a lazily loaded `<img>`.
+///
+/// `:alt`, `:width` and the other `#+ATTR_HTML:` attributes of `element` are
+/// applied. Missing dimensions are read from the image file, scaled to keep
+/// its ratio when only one is given.
+///
+/// Images with resized [`images::variants`] are wrapped on a `<picture>`
+/// offering them, AVIF first. `:sizes` defaults to the image width.
+pub fn img_html(
+    link: &Link,
+    element: Option<&SyntaxNode>,
+    site: &Site,
+    file_rel_path: &Path,
+) -> String {
+    let path = link.path();
+    let (src, source) = source(&path, site, file_rel_path);
+
+    let attributes = element
+        .and_then(|e| page::affiliated_keyword(e, "ATTR_HTML"))
+        .map(|a| code::parse_parameters(&a))
+        .unwrap_or_default();
+    let attribute = |key: &str| {
+        attributes
+            .iter()
+            .find(|(k, _)| k.trim_start_matches(':').eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    };
+
+    let size = source
+        .as_ref()
+        .and_then(|s| intrinsic_size(&site.source_path(s), file_rel_path));
+    let (width, height) = dimensions(attribute("width"), attribute("height"), size);
+    let variants = match (&source, size) {
+        (Some(source), Some((intrinsic_width, _))) => images::variants(source, intrinsic_width),
+        _ => vec![],
+    };
+
+    let mut html = String::new();
+    let mut img_src = src.clone();
+    if !variants.is_empty() {
+        let intrinsic_width = size.map(|(w, _)| w).unwrap_or_default();
+        let sizes = attribute("sizes").map(String::from).unwrap_or_else(|| {
+            match width.as_deref().filter(|w| w.parse::<u32>().is_ok()) {
+                Some(width) => format!("(max-width: {width}px) 100vw, {width}px"),
+                None => String::from("100vw"),
+            }
+        });
+        // Images wider than every variant aren't offered at full size
+        let original = images::WIDTHS
+            .last()
+            .is_some_and(|w| intrinsic_width <= *w)
+            .then_some(intrinsic_width);
+        if original.is_none()
+            && let Some(widest) = variants.iter().rfind(|v| !v.avif)
+        {
+            img_src = images::variant_src(&src, widest);
+        }
+
+        let _ = write!(
+            html,
+            r#"<picture><source type="image/avif" srcset="{}" sizes="{}">"#,
+            HtmlEscape(images::srcset(&src, &variants, true, None)),
+            HtmlEscape(&sizes),
+        );
+        let _ = write!(
+            html,
+            r#"<img src="{}" srcset="{}" sizes="{}""#,
+            HtmlEscape(&img_src),
+            HtmlEscape(images::srcset(&src, &variants, false, original)),
+            HtmlEscape(&sizes),
+        );
+    } else {
+        let _ = write!(html, r#"<img src="{}""#, HtmlEscape(&src));
+    }
+
+    match attribute("alt") {
+        Some(alt) => {
+            let _ = write!(html, r#" alt="{}""#, HtmlEscape(alt));
+        }
+        None => log::warn!(
+            "{}: Image '{src}' has no alt text, add one with '#+ATTR_HTML: :alt'",
+            file_rel_path.display()
+        ),
+    }
+    if let Some(width) = width {
+        let _ = write!(html, r#" width="{}""#, HtmlEscape(width));
+    }
+    if let Some(height) = height {
+        let _ = write!(html, r#" height="{}""#, HtmlEscape(height));
+    }
+    if attribute("loading").is_none() {
+        html.push_str(r#" loading="lazy""#);
+    }
+
+    for (key, value) in &attributes {
+        let key = key.trim_start_matches(':');
+        if ["alt", "width", "height", "sizes"].contains(&key) || RESERVED_ATTRIBUTES.contains(&key)
+        {
+            continue;
+        }
+        let _ = write!(html, r#" {}="{}""#, HtmlEscape(key), HtmlEscape(value));
+    }
+    html.push('>');
+    if !variants.is_empty() {
+        html.push_str("</picture>");
+    }
+    html
+}
+
+/// Site files of the local images shown on `doc`, the ones offered resized.
+pub fn images(doc: &Org, site: &Site, file_rel_path: &Path) -> Vec<PathBuf> {
+    doc.document()
+        .syntax()
+        .descendants()
+        .filter_map(Link::cast)
+        .filter(|link| link.is_image())
+        .filter_map(
+            |link| match links::target(&link.path(), site, file_rel_path) {
+                Target::Asset(src) => Some(PathBuf::from(src.trim_start_matches('/'))),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+/// `src` of an image link, and the site file it points to, if local.
+///
+/// Local images are resolved like any other link, so `./image.png` on
+/// `b/post.org` is `/b/image.png`, and `./post/image.png` is `/b/post/image.png`.
+fn source(path: &str, site: &Site, file_rel_path: &Path) -> (String, Option<PathBuf>) {
+    match links::resolve(path, site, file_rel_path) {
+        Target::Asset(src) => {
+            let rel_path = PathBuf::from(src.trim_start_matches('/'));
+            (src, Some(rel_path))
+        }
+        _ => (path.to_string(), None),
+    }
+}
+
+/// Width and height of an image file, if it can be read.
+fn intrinsic_size(path: &Path, file_rel_path: &Path) -> Option<(u32, u32)> {
+    // Vector images scale to whatever they're given
+    if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("svg"))
+    {
+        return None;
+    }
+    match image::image_dimensions(path) {
+        Ok(size) => Some(size),
+        Err(error) => {
+            log::warn!(
+                "{}: Can't read the size of image '{}': {error}",
+                file_rel_path.display(),
+                path.display()
+            );
+            None
+        }
+    }
+}
+
+/// `width` and `height` attributes, from the given ones and the intrinsic size
+fn dimensions(
+    width: Option<&str>,
+    height: Option<&str>,
+    size: Option<(u32, u32)>,
+) -> (Option<String>, Option<String>) {
+    let scaled = |given: &str, from: u32, to: u32| {
+        let given: f64 = given.parse().ok()?;
+        (from > 0).then(|| ((given * f64::from(to) / f64::from(from)).round() as u32).to_string())
+    };
+
+    match (width, height, size) {
+        (None, None, Some((w, h))) => (Some(w.to_string()), Some(h.to_string())),
+        (Some(width), None, Some((w, h))) => (Some(width.to_string()), scaled(width, w, h)),
+        (None, Some(height), Some((w, h))) => (scaled(height, h, w), Some(height.to_string())),
+        (width, height, _) => (width.map(String::from), height.map(String::from)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use indoc::indoc;
+    use orgize::Org;
+
+    #[gtest]
+    fn sources() {
+        let site = Site::default();
+        let path = Path::new("b/post.org");
+        for (link, src) in [
+            ("./post/image.png", "/b/post/image.png"),
+            ("./x.png", "/b/x.png"),
+            ("../i/x.png", "/i/x.png"),
+            ("file:/i/512.png", "/i/512.png"),
+        ] {
+            expect_that!(
+                source(link, &site, path),
+                eq(&(
+                    String::from(src),
+                    Some(PathBuf::from(src.trim_start_matches('/')))
+                )),
+                "{link}"
+            );
+        }
+        expect_that!(
+            source("https://example.com/a.png", &site, path),
+            eq(&(String::from("https://example.com/a.png"), None))
+        );
+    }
+
+    #[gtest]
+    fn shown_images() {
+        let doc = Org::parse(
+            "[[./a.png]] [[../i/b.webp]] [[./notes.org]] [[./c.pdf]] [[https://example.com/d.png]]\n",
+        );
+        expect_that!(
+            images(&doc, &Site::default(), Path::new("b/post.org")),
+            elements_are![eq(Path::new("b/a.png")), eq(Path::new("i/b.webp"))]
+        );
+    }
+
+    #[gtest]
+    fn scaled_dimensions() {
+        let size = Some((512, 256));
+        expect_that!(
+            dimensions(None, None, size),
+            eq(&(Some(String::from("512")), Some(String::from("256"))))
+        );
+        expect_that!(
+            dimensions(Some("100"), None, size),
+            eq(&(Some(String::from("100")), Some(String::from("50"))))
+        );
+        expect_that!(
+            dimensions(Some("50%"), None, size),
+            eq(&(Some(String::from("50%")), None))
+        );
+        expect_that!(dimensions(None, None, None), eq(&(None, None)));
+    }
+
+    #[gtest]
+    fn figure() {
+        let doc = Org::parse(indoc! {r###"
+          #+CAPTION: The /site/ icon
+          #+NAME: icon
+          #+ATTR_HTML: :alt A blue square :width 64 :class icon
+          [[/i/512.png]]
+
+          Inline [[/i/16.png]] image.
+        "###});
+        let site = Site::new().dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("site").as_path());
+        let path = Path::new("about.org");
+
+        let paragraphs: Vec<Paragraph> = doc
+            .document()
+            .syntax()
+            .descendants()
+            .filter_map(Paragraph::cast)
+            .collect();
+        let link = standalone_image(&paragraphs[0]);
+        expect_that!(standalone_image(&paragraphs[1]), none());
+        expect_that!(
+            to_html(&paragraphs[0], &link.unwrap(), &site, path),
+            eq(concat!(
+                r#"<figure id="icon">"#,
+                "<picture>",
+                r#"<source type="image/avif" srcset="/i/512-480w.avif 480w, /i/512-512w.avif 512w" sizes="(max-width: 64px) 100vw, 64px">"#,
+                r#"<img src="/i/512.png" srcset="/i/512-480w.png 480w, /i/512.png 512w" sizes="(max-width: 64px) 100vw, 64px""#,
+                r#" alt="A blue square" width="64" height="64" loading="lazy" class="icon">"#,
+                "</picture>",
+                r#"<figcaption>The <i>site</i> icon</figcaption>"#,
+                "</figure>"
+            ))
+        );
+    }
+}
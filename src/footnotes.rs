@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use orgize::ast::{FnDef, FnRef};
+use orgize::rowan::ast::AstNode;
+use orgize::{Org, SyntaxElement, SyntaxKind, SyntaxNode, TextRange};
+use slugify::slugify;
+
+use crate::page;
+
+/// A footnote, numbered by its first reference
+#[derive(Debug)]
+struct Note {
+    /// `name` on `[fn:name]`, `None` for anonymous inline notes
+    label: Option<String>,
+    /// Inline HTML
+    definition: Option<String>,
+    /// Times it's referenced, each one gets a backlink
+    references: usize,
+}
+
+/// Footnotes of a page, renumbered in reference order.
+///
+/// Ids are prefixed with the page slug so they don't collide when several
+/// fragments end up on the same `#content`.
+#[derive(Debug)]
+pub struct Footnotes {
+    scope: String,
+    /// Definitions by label, from `[fn:label] ...` lines
+    definitions: HashMap<String, String>,
+    /// Lines continuing a definition, rendered with it
+    continuations: HashSet<TextRange>,
+    notes: Vec<Note>,
+}
+
+impl Footnotes {
+    pub fn new(doc: &Org, file_rel_path: &Path) -> Self {
+        let mut definitions = HashMap::new();
+        let mut continuations = HashSet::new();
+
+        for def in doc
+            .document()
+            .syntax()
+            .descendants()
+            .filter_map(FnDef::cast)
+        {
+            let Some(label) = label(def.syntax()) else {
+                continue;
+            };
+
+            // The definition goes on until a blank line, but only its first
+            // line is part of the `FnDef`
+            let mut text: String = def
+                .syntax()
+                .children_with_tokens()
+                .skip_while(|e| e.kind() != SyntaxKind::R_BRACKET)
+                .skip(1)
+                .filter(|e| e.kind() == SyntaxKind::TEXT)
+                .map(|e| e.to_string())
+                .collect();
+            if def.post_blank() == 0
+                && let Some(next) = def.syntax().next_sibling()
+                && next.kind() == SyntaxKind::PARAGRAPH
+            {
+                text.push('\n');
+                text.push_str(next.to_string().trim_end());
+                continuations.insert(next.text_range());
+            }
+
+            definitions.insert(label, page::title_html(text.trim()));
+        }
+
+        Self {
+            scope: slugify!(&file_rel_path.with_extension("").to_string_lossy()),
+            definitions,
+            continuations,
+            notes: vec![],
+        }
+    }
+
+    /// Whether a node continues a definition and was rendered with it
+    pub fn is_continuation(&self, node: &SyntaxNode) -> bool {
+        self.continuations.contains(&node.text_range())
+    }
+
+    /// Renders a reference as a superscript link to its note.
+    pub fn reference(&mut self, reference: &FnRef) -> String {
+        let label = label(reference.syntax());
+        let inline = inline_definition(reference.syntax());
+
+        let existing = label
+            .as_ref()
+            .and_then(|l| self.notes.iter().position(|n| n.label.as_ref() == Some(l)));
+        let index = match existing {
+            Some(index) => {
+                let note = &mut self.notes[index];
+                note.references += 1;
+                if note.definition.is_none() {
+                    note.definition = inline;
+                }
+                index
+            }
+            None => {
+                let definition = inline.or_else(|| {
+                    label
+                        .as_ref()
+                        .and_then(|l| self.definitions.get(l).cloned())
+                });
+                if definition.is_none() {
+                    log::warn!(
+                        "{}: Footnote [fn:{}] has no definition",
+                        self.scope,
+                        label.as_deref().unwrap_or_default()
+                    );
+                }
+                self.notes.push(Note {
+                    label,
+                    definition,
+                    references: 1,
+                });
+                self.notes.len() - 1
+            }
+        };
+
+        let n = index + 1;
+        format!(
+            r##"<sup class="footnote-ref"><a id="{}" href="#{}" role="doc-noteref">{n}</a></sup>"##,
+            self.reference_id(n, self.notes[index].references),
+            self.note_id(n),
+        )
+    }
+
+    fn note_id(&self, n: usize) -> String {
+        format!("{}-fn-{n}", self.scope)
+    }
+
+    /// Id of the k-th reference to the n-th note
+    fn reference_id(&self, n: usize, k: usize) -> String {
+        if k <= 1 {
+            format!("{}-fnref-{n}", self.scope)
+        } else {
+            format!("{}-fnref-{n}-{k}", self.scope)
+        }
+    }
+
+    /// Renders the "Notes" section, with a `depth` heading, or nothing if
+    /// there were no references.
+    pub fn to_html(&self, depth: i8) -> String {
+        if self.notes.is_empty() {
+            return String::new();
+        }
+
+        let heading = page::heading(depth);
+        let mut html = format!(
+            r#"<section class="footnotes" role="doc-endnotes"><{heading}>Notes</{heading}><ol>"#
+        );
+        for (i, note) in self.notes.iter().enumerate() {
+            let n = i + 1;
+            let _ = write!(
+                html,
+                r#"<li id="{}">{}"#,
+                self.note_id(n),
+                note.definition.as_deref().unwrap_or_default()
+            );
+            for k in 1..=note.references {
+                let _ = write!(
+                    html,
+                    r##" <a href="#{}" role="doc-backlink">↩{}</a>"##,
+                    self.reference_id(n, k),
+                    if k > 1 {
+                        format!("<sup>{k}</sup>")
+                    } else {
+                        String::new()
+                    }
+                );
+            }
+            html.push_str("</li>");
+        }
+        html.push_str("</ol></section>");
+        html
+    }
+}
+
+/// `label` on `[fn:label]` or `[fn:label:definition]`, if not empty
+fn label(node: &SyntaxNode) -> Option<String> {
+    node.children_with_tokens()
+        .nth(3)
+        .filter(|e| e.kind() == SyntaxKind::TEXT)
+        .map(|e| e.to_string())
+        .filter(|l| !l.is_empty())
+}
+
+/// `definition` on `[fn::definition]` or `[fn:label:definition]`, as inline HTML
+fn inline_definition(node: &SyntaxNode) -> Option<String> {
+    let mut elements = node.children_with_tokens().skip(4);
+    if elements.next().map(|e| e.kind()) != Some(SyntaxKind::COLON) {
+        return None;
+    }
+    let definition: String = elements
+        .filter(|e| e.kind() != SyntaxKind::R_BRACKET)
+        .map(|e: SyntaxElement| e.to_string())
+        .collect();
+    Some(page::title_html(definition.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::site::Site;
+    use googletest::prelude::*;
+    use indoc::indoc;
+
+    #[gtest]
+    fn renumbered_with_backlinks() {
+        let doc = Org::parse(indoc! {r###"
+          Named[fn:later], inline[fn::An *inline* note] and again[fn:later].
+
+          [fn:later] Defined
+          on two lines
+
+          [fn:unused] Never referenced
+        "###});
+
+        let html = page::to_html(&doc, &[], Path::new("b/post.org"), &Site::default());
+        expect_that!(
+            html,
+            ok(all![
+                contains_substring(
+                    r##"Named<sup class="footnote-ref"><a id="b-post-fnref-1" href="#b-post-fn-1" role="doc-noteref">1</a></sup>"##
+                ),
+                contains_substring(r##"<a id="b-post-fnref-2" href="#b-post-fn-2""##),
+                contains_substring(r##"<a id="b-post-fnref-1-2" href="#b-post-fn-1""##),
+                contains_substring(concat!(
+                    r##"<section class="footnotes" role="doc-endnotes"><h1>Notes</h1><ol>"##,
+                    r##"<li id="b-post-fn-1">Defined"##,
+                    "\n",
+                    r##"on two lines <a href="#b-post-fnref-1" role="doc-backlink">↩</a> <a href="#b-post-fnref-1-2" role="doc-backlink">↩<sup>2</sup></a></li>"##,
+                    r##"<li id="b-post-fn-2">An <b>inline</b> note <a href="#b-post-fnref-2" role="doc-backlink">↩</a></li>"##,
+                    "</ol></section>",
+                )),
+                not(contains_substring("Never referenced")),
+                not(contains_substring("<p>on two lines")),
+            ])
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::LazyLock;
+
+use orgize::export::HtmlEscape;
+use syntect::highlighting::ThemeSet;
+use syntect::html::{ClassStyle, css_for_theme_with_class_style, line_tokens_to_classed_spans};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+/// Theme used when none is given
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// Site path of the highlighting stylesheet
+pub const CSS_PATH: &str = "s/highlight.css";
+
+/// Classes on the highlighted tokens are prefixed to avoid clashing with the site CSS
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// Org `#+begin_src` languages named differently on the syntax set
+static LANGUAGE_ALIASES: &[(&str, &str)] = &[
+    ("elisp", "lisp"),
+    ("emacs-lisp", "lisp"),
+    ("shell", "sh"),
+    ("bash", "sh"),
+    ("zsh", "sh"),
+    ("javascript", "js"),
+];
+
+/// Names of the themes that can be used
+pub fn themes() -> impl Iterator<Item = &'static str> {
+    THEMES.themes.keys().map(|t| t.as_str())
+}
+
+/// Stylesheet for the highlighted tokens, `None` if the theme is unknown.
+///
+/// Also styles the `line`, `hl-emphasis` and `data-line` marks of [`crate::code`].
+pub fn css(theme: &str) -> Option<String> {
+    let theme = THEMES.themes.get(theme)?;
+    let mut css = css_for_theme_with_class_style(theme, CLASS_STYLE).ok()?;
+
+    let emphasis = theme.settings.line_highlight.map_or_else(
+        || String::from("rgba(127, 127, 127, 0.2)"),
+        |c| {
+            format!(
+                "rgba({}, {}, {}, {:.2})",
+                c.r,
+                c.g,
+                c.b,
+                f32::from(c.a) / 255.0
+            )
+        },
+    );
+    css.push_str(&indoc::formatdoc! {"
+        .hl-code .line {{ display: inline-block; width: 100%; }}
+        .hl-code .hl-emphasis {{ background-color: {emphasis}; }}
+        .hl-code .line[data-line]::before {{
+          content: attr(data-line);
+          display: inline-block;
+          width: 3em;
+          padding-right: 1em;
+          text-align: right;
+          opacity: 0.5;
+          user-select: none;
+        }}
+    "});
+    Some(css)
+}
+
+fn syntax(language: &str) -> Option<&'static SyntaxReference> {
+    let language = LANGUAGE_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(language))
+        .map_or(language, |(_, name)| name);
+    SYNTAXES.find_syntax_by_token(language)
+}
+
+/// Highlights a source block as `<span class>` tokens, to go inside `<code>`.
+///
+/// Unknown languages are only escaped.
+pub fn to_html(code: &str, language: Option<&str>) -> String {
+    lines(code, language)
+        .into_iter()
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// Highlights a source block line by line, without the line endings.
+///
+/// Every line closes the tokens it opens so it can be wrapped on its own element.
+pub fn lines(code: &str, language: Option<&str>) -> Vec<String> {
+    let escaped = || code.lines().map(|l| HtmlEscape(l).to_string()).collect();
+    let Some(syntax) = language.and_then(syntax) else {
+        return escaped();
+    };
+
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut lines = vec![];
+    for line in LinesWithEndings::from(code) {
+        let highlighted = state
+            .parse_line(line, &SYNTAXES)
+            .map_err(|e| e.to_string())
+            .and_then(|ops| {
+                // Reopen the tokens left open by the previous line
+                let mut html = String::new();
+                for scope in stack.as_slice() {
+                    let classes: Vec<String> = scope
+                        .build_string()
+                        .split('.')
+                        .map(|atom| format!("hl-{atom}"))
+                        .collect();
+                    html.push_str(&format!(r#"<span class="{}">"#, classes.join(" ")));
+                }
+                let (spans, _) = line_tokens_to_classed_spans(line, &ops, CLASS_STYLE, &mut stack)
+                    .map_err(|e| e.to_string())?;
+                html.push_str(&spans);
+                html.push_str(&"</span>".repeat(stack.len()));
+                Ok(html)
+            });
+
+        match highlighted {
+            // Markup has no line breaks, so the last one is the line ending
+            Ok(mut html) => {
+                if let Some(at) = html.rfind('\n') {
+                    html.remove(at);
+                    if html[..at].ends_with('\r') {
+                        html.remove(at - 1);
+                    }
+                }
+                lines.push(html);
+            }
+            Err(e) => {
+                log::warn!("Can't highlight {} code: {e}", syntax.name);
+                return escaped();
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    #[gtest]
+    fn highlights_known_languages() {
+        expect_that!(
+            to_html("fn main() {}\n", Some("rust")),
+            all![
+                starts_with(r#"<span class="hl-source hl-rust">"#),
+                contains_substring(
+                    r#"<span class="hl-storage hl-type hl-function hl-rust">fn</span>"#
+                ),
+            ]
+        );
+        expect_that!(
+            to_html("echo hi\n", Some("shell")),
+            starts_with(r#"<span class="hl-source hl-shell"#)
+        );
+        expect_that!(
+            to_html("<b>\n", Some("no-such-language")),
+            eq("&lt;b&gt;\n")
+        );
+        expect_that!(to_html("<b>\n", None), eq("&lt;b&gt;\n"));
+    }
+
+    #[gtest]
+    fn lines_are_balanced() {
+        let lines = lines("/* a\n b */\nfn main() {}\n", Some("rust"));
+        expect_that!(lines.len(), eq(3));
+        for line in &lines {
+            expect_that!(
+                line.matches("<span").count(),
+                eq(line.matches("</span>").count())
+            );
+            expect_that!(line, not(contains_substring("\n")));
+        }
+        expect_that!(
+            lines[1],
+            starts_with(
+                r#"<span class="hl-source hl-rust"><span class="hl-comment hl-block hl-rust">"#
+            )
+        );
+    }
+
+    #[gtest]
+    fn theme_css() {
+        expect_that!(css(DEFAULT_THEME), some(contains_substring(".hl-comment")));
+        expect_that!(css("no-such-theme"), none());
+    }
+}
@@ -0,0 +1,259 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+
+/// Widths images are resized to, in pixels.
+///
+/// The background variants on `site/s/_.css`, and their preloads on
+/// `site/index.html`, are named after these.
+pub const WIDTHS: &[u32] = &[480, 960, 1920];
+
+/// 0 to 100
+const AVIF_QUALITY: u8 = 70;
+/// 1, slowest, to 10
+const AVIF_SPEED: u8 = 8;
+const JPEG_QUALITY: u8 = 80;
+
+/// Extensions of the images that get resized variants
+const RASTER_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// A resized copy of an image, next to it on the site.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// File name, like `photo-480w.avif`
+    pub file_name: String,
+    pub width: u32,
+    /// AVIF, or else the fallback format for browsers without it
+    pub avif: bool,
+}
+
+/// Whether `path` is an image that gets resized variants
+pub fn is_raster(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| RASTER_EXTENSIONS.iter().any(|r| r.eq_ignore_ascii_case(e)))
+}
+
+/// Variants of the image at `path`, `width` pixels wide.
+///
+/// Each of [`WIDTHS`] narrower than the image gets an AVIF and a copy in the
+/// original format, or JPEG for WebP. The image at full width gets an AVIF too, unless it's
+/// wider than every width. Images narrower than every width get nothing.
+pub fn variants(path: &Path, width: u32) -> Vec<Variant> {
+    let (Some(stem), Some(extension)) = (path.file_stem(), path.extension()) else {
+        return vec![];
+    };
+    if !is_raster(path) || WIDTHS.first().is_none_or(|w| width <= *w) {
+        return vec![];
+    }
+    let stem = stem.to_string_lossy();
+    // Lossless WebP photos weigh more than the original, `image` can't
+    // encode lossy ones
+    let extension = match extension.to_string_lossy() {
+        e if e.eq_ignore_ascii_case("webp") => "jpg".into(),
+        e => e,
+    };
+
+    let mut widths: Vec<u32> = WIDTHS.iter().copied().filter(|w| *w < width).collect();
+    if WIDTHS.last().is_some_and(|w| width <= *w) {
+        widths.push(width);
+    }
+
+    let mut variants = vec![];
+    for w in widths {
+        variants.push(Variant {
+            file_name: format!("{stem}-{w}w.avif"),
+            width: w,
+            avif: true,
+        });
+        if w < width {
+            variants.push(Variant {
+                file_name: format!("{stem}-{w}w.{extension}"),
+                width: w,
+                avif: false,
+            });
+        }
+    }
+    variants
+}
+
+/// Writes the variants of the image `source` next to its copy at `out_path`.
+///
+/// Variants newer than `source` are kept from previous builds, unless `force`.
+/// Images that can't be decoded are logged and skipped.
+pub fn generate(source: &Path, out_path: &Path, force: bool) -> io::Result<()> {
+    let (width, _) = match image::image_dimensions(source) {
+        Ok(size) => size,
+        Err(error) => {
+            log::warn!("Can't read image '{}': {error}", source.display());
+            return Ok(());
+        }
+    };
+
+    let modified = fs::metadata(source)?.modified()?;
+    let dir = out_path.parent().unwrap_or(Path::new(""));
+    let mut pending: Vec<(Variant, PathBuf)> = vec![];
+    for variant in variants(source, width) {
+        let variant_path = dir.join(&variant.file_name);
+        let up_to_date = fs::metadata(&variant_path)
+            .and_then(|m| m.modified())
+            .is_ok_and(|m| m >= modified);
+        if force || !up_to_date {
+            pending.push((variant, variant_path));
+        } else {
+            log::debug!(
+                "Skipping writing of up-to-date file '{}'",
+                variant_path.display()
+            );
+        }
+    }
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let image = match image::open(source) {
+        Ok(image) => image,
+        Err(error) => {
+            log::warn!("Can't decode image '{}': {error}", source.display());
+            return Ok(());
+        }
+    };
+    let format = ImageFormat::from_path(source).map_err(io::Error::other)?;
+    for (variant, variant_path) in pending {
+        log::info!("Will write '{}'", variant_path.display());
+        let resized = if variant.width < image.width() {
+            image.resize(variant.width, u32::MAX, FilterType::Lanczos3)
+        } else {
+            image.clone()
+        };
+        // Encoders only take 8 bit RGB(A)
+        let resized = if resized.color().has_alpha() && (variant.avif || format == ImageFormat::Png)
+        {
+            DynamicImage::ImageRgba8(resized.to_rgba8())
+        } else {
+            DynamicImage::ImageRgb8(resized.to_rgb8())
+        };
+
+        let mut encoded = io::Cursor::new(vec![]);
+        let written = if variant.avif {
+            resized.write_with_encoder(AvifEncoder::new_with_speed_quality(
+                &mut encoded,
+                AVIF_SPEED,
+                AVIF_QUALITY,
+            ))
+        } else if format == ImageFormat::Png {
+            resized.write_to(&mut encoded, format)
+        } else {
+            resized.write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY))
+        };
+        if let Err(error) = written {
+            log::error!("Can't encode '{}': {error}", variant_path.display());
+            continue;
+        }
+
+        // Renamed once complete, so an interrupted build doesn't leave a
+        // truncated variant that looks up to date
+        let partial_path = dir.join(format!(".{}.part", variant.file_name));
+        fs::write(&partial_path, encoded.into_inner())?;
+        fs::rename(&partial_path, &variant_path)?;
+    }
+    Ok(())
+}
+
+/// Link to `variant`, next to the image at `src`
+pub fn variant_src(src: &str, variant: &Variant) -> String {
+    match src.rsplit_once('/') {
+        Some((dir, _)) => format!("{dir}/{}", variant.file_name),
+        None => variant.file_name.clone(),
+    }
+}
+
+/// `srcset` of `variants`, linked next to the image at `src`.
+///
+/// `original` is the width of the image itself, listed with the variants in
+/// its format.
+pub fn srcset(src: &str, variants: &[Variant], avif: bool, original: Option<u32>) -> String {
+    let mut candidates: Vec<String> = variants
+        .iter()
+        .filter(|v| v.avif == avif)
+        .map(|v| format!("{} {}w", variant_src(src, v), v.width))
+        .collect();
+    if let Some(width) = original {
+        candidates.push(format!("{src} {width}w"));
+    }
+    candidates.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    fn names(variants: &[Variant]) -> Vec<&str> {
+        variants.iter().map(|v| v.file_name.as_str()).collect()
+    }
+
+    #[gtest]
+    fn variant_widths() {
+        expect_that!(
+            names(&variants(Path::new("s/hero.webp"), 3888)),
+            eq(&vec![
+                "hero-480w.avif",
+                "hero-480w.jpg",
+                "hero-960w.avif",
+                "hero-960w.jpg",
+                "hero-1920w.avif",
+                "hero-1920w.jpg",
+            ])
+        );
+        expect_that!(
+            names(&variants(Path::new("i/512.png"), 512)),
+            eq(&vec!["512-480w.avif", "512-480w.png", "512-512w.avif"])
+        );
+        expect_that!(variants(Path::new("i/32.png"), 32), is_empty());
+        expect_that!(variants(Path::new("i/logo.svg"), 1000), is_empty());
+    }
+
+    #[gtest]
+    fn srcsets() {
+        let variants = variants(Path::new("i/512.png"), 512);
+        expect_that!(
+            srcset("/i/512.png", &variants, true, None),
+            eq("/i/512-480w.avif 480w, /i/512-512w.avif 512w")
+        );
+        expect_that!(
+            srcset("512.png", &variants, false, Some(512)),
+            eq("512-480w.png 480w, 512.png 512w")
+        );
+    }
+
+    #[gtest]
+    fn generates_and_keeps_variants() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("blorg-images-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let source = dir.join("wide.png");
+        image::RgbImage::new(600, 20).save(&source)?;
+
+        generate(&source, &source, false)?;
+        let avif = dir.join("wide-480w.avif");
+        expect_that!(
+            image::image_dimensions(dir.join("wide-480w.png"))?,
+            eq((480, 16))
+        );
+        expect_that!(avif.exists(), eq(true));
+        expect_that!(dir.join("wide-600w.avif").exists(), eq(true));
+
+        // Kept while newer than the source
+        let written = fs::metadata(&avif)?.modified()?;
+        generate(&source, &source, false)?;
+        expect_that!(fs::metadata(&avif)?.modified()?, eq(written));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
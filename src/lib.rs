@@ -1,2 +1,20 @@
+pub mod anchors;
+pub mod archive;
+pub mod backlinks;
+pub mod check;
+pub mod code;
 pub mod feed;
+pub mod figure;
+pub mod footnotes;
+pub mod highlight;
+pub mod images;
+pub mod links;
+pub mod math;
+pub mod nav;
 pub mod page;
+pub mod publish;
+pub mod site;
+pub mod table;
+pub mod tags;
+pub mod tangle;
+pub mod toc;
@@ -0,0 +1,281 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+
+use orgize::ast::Link;
+use orgize::rowan::ast::AstNode;
+use regex::Regex;
+
+use crate::site::{Page, Site};
+
+/// `scheme:` of a URI, like `https:` or `mailto:`, but not `page.org::search`
+static SCHEME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*:([^:]|$)").expect("Valid regex"));
+
+/// Where a link points to.
+#[derive(Debug, PartialEq)]
+pub enum Target<'a> {
+    /// Another site, or a non-file link like `mailto:`
+    External,
+    /// A published page, and the anchor and title of one of its sections
+    Page {
+        page: &'a Page,
+        section: Option<(&'a str, &'a str)>,
+    },
+    /// A file copied to the site, by its site path
+    Asset(String),
+    /// A missing, unpublished or draft target. Its title isn't given, as it
+    /// may be private.
+    Broken,
+}
+
+/// A link of a page, resolved once every page is known and kept on the [`Site`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageLink {
+    /// Link as written, like `../about.org::*Contact`
+    pub path: String,
+    /// Line of the link on its `.org` file
+    pub line: usize,
+    /// Offset of the link on its document, to find it from its node
+    pub offset: usize,
+    target: Resolved,
+}
+
+/// [`Target`], with pages by their path
+#[derive(Debug, Clone, PartialEq)]
+enum Resolved {
+    External,
+    Page {
+        rel_path: PathBuf,
+        section: Option<(String, String)>,
+    },
+    Asset(String),
+    Broken,
+}
+
+impl PageLink {
+    /// Resolves `link` on `source`, the text of the page at `file_rel_path`,
+    /// without reporting anything.
+    pub fn new(link: &Link, source: &str, site: &Site, file_rel_path: &Path) -> Self {
+        let path = link.path().to_string();
+        let target = match target(&path, site, file_rel_path) {
+            Target::External => Resolved::External,
+            Target::Page { page, section } => Resolved::Page {
+                rel_path: page.rel_path.clone(),
+                section: section.map(|(anchor, title)| (anchor.to_string(), title.to_string())),
+            },
+            Target::Asset(src) => Resolved::Asset(src),
+            Target::Broken => Resolved::Broken,
+        };
+        Self {
+            line: line(source, link),
+            offset: offset(link),
+            path,
+            target,
+        }
+    }
+
+    /// Where the link points to on `site`. Pages that are no longer
+    /// published, like the ones cut by [`crate::publish::Subgraph`], are broken.
+    pub fn target<'a>(&'a self, site: &'a Site) -> Target<'a> {
+        match &self.target {
+            Resolved::External => Target::External,
+            Resolved::Page { rel_path, section } => match site.get(rel_path) {
+                Some(page) => Target::Page {
+                    page,
+                    section: section
+                        .as_ref()
+                        .map(|(anchor, title)| (anchor.as_str(), title.as_str())),
+                },
+                None => Target::Broken,
+            },
+            Resolved::Asset(src) => Target::Asset(src.clone()),
+            Resolved::Broken => Target::Broken,
+        }
+    }
+}
+
+/// Resolves a link on the page at `file_rel_path`.
+///
+/// `id:` links go to the page or section with that `ID`. `file:` and relative
+/// links are resolved against the page directory, and `/` ones against the
+/// site directory. `::*Heading` and `::#custom-id` search options go to the
+/// section. Missing, unpublished and draft targets are reported, and broken.
+pub fn resolve<'a>(link: &str, site: &'a Site, file_rel_path: &Path) -> Target<'a> {
+    let (target, problem) = locate(link, site, file_rel_path);
+    if let Some(problem) = problem {
+        log::warn!("{}: {problem}", file_rel_path.display());
+    }
+    target
+}
+
+/// Like [`resolve`], without reporting anything.
+pub fn target<'a>(link: &str, site: &'a Site, file_rel_path: &Path) -> Target<'a> {
+    locate(link, site, file_rel_path).0
+}
+
+/// Line of `link` on `source`, the text of its document
+pub fn line(source: &str, link: &Link) -> usize {
+    source[..offset(link)].matches('\n').count() + 1
+}
+
+/// Offset of `link` on its document
+pub fn offset(link: &Link) -> usize {
+    usize::from(link.syntax().text_range().start())
+}
+
+/// Whether `link` has a scheme other than `file:`, like `https:` or `mailto:`
+pub fn is_external(link: &str) -> bool {
+    SCHEME.is_match(link) && !link.starts_with("file:")
+}
+
+/// Target of a link, and what's wrong with it, if anything
+fn locate<'a>(link: &str, site: &'a Site, file_rel_path: &Path) -> (Target<'a>, Option<String>) {
+    if let Some(id) = link.strip_prefix("id:") {
+        return locate_id(id, site);
+    }
+    let path = match link.strip_prefix("file:") {
+        Some(path) => path,
+        None if is_external(link) => return (Target::External, None),
+        None => link,
+    };
+    let (path, search) = match path.split_once("::") {
+        Some((path, search)) => (path, Some(search)),
+        None => (path, None),
+    };
+
+    let rel_path = match path {
+        // Search on the page itself
+        "" => Some(file_rel_path.to_path_buf()),
+        _ => match path.strip_prefix('/') {
+            Some(site_path) => normalize(Path::new(site_path)),
+            None => normalize(&file_rel_path.parent().unwrap_or(Path::new("")).join(path)),
+        },
+    };
+    let Some(rel_path) = rel_path else {
+        let problem = format!("Link '{link}' points outside of the site");
+        return (Target::Broken, Some(problem));
+    };
+
+    if rel_path.extension().is_none_or(|e| e != "org") {
+        return (Target::Asset(format!("/{}", rel_path.display())), None);
+    }
+    let page = match published(site, &rel_path, link) {
+        Ok(page) => page,
+        Err(problem) => return (Target::Broken, Some(problem)),
+    };
+
+    let mut problem = None;
+    let section = search.and_then(|search| site.anchors(&rel_path)?.resolve(search));
+    if search.is_some() && section.is_none() {
+        problem = Some(format!(
+            "Can't find the section linked by '{link}', linking to the page"
+        ));
+    }
+    (Target::Page { page, section }, problem)
+}
+
+fn locate_id<'a>(id: &str, site: &'a Site) -> (Target<'a>, Option<String>) {
+    let Some(target) = site.id(id) else {
+        let problem = format!("Can't find the target of 'id:{id}'");
+        return (Target::Broken, Some(problem));
+    };
+    let page = match published(site, &target.rel_path, &format!("id:{id}")) {
+        Ok(page) => page,
+        Err(problem) => return (Target::Broken, Some(problem)),
+    };
+    let section = target.anchor.as_deref().map(|a| (a, target.title.as_str()));
+    (Target::Page { page, section }, None)
+}
+
+/// The page at `rel_path`, or else whether it's unpublished or a draft
+fn published<'a>(site: &'a Site, rel_path: &Path, link: &str) -> Result<&'a Page, String> {
+    match site.get(rel_path) {
+        None => Err(format!(
+            "'{link}' links to unpublished '{}'",
+            rel_path.display()
+        )),
+        Some(page) if page.draft => {
+            Err(format!("'{link}' links to draft '{}'", rel_path.display()))
+        }
+        Some(page) => Ok(page),
+    }
+}
+
+/// Drops the `.` and `..` of a relative path. `None` if it leaves the site.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    #[gtest]
+    fn normalized() {
+        expect_that!(
+            normalize(Path::new("b/../about.org")),
+            some(eq(Path::new("about.org")))
+        );
+        expect_that!(
+            normalize(Path::new("b/./post/i.png")),
+            some(eq(Path::new("b/post/i.png")))
+        );
+        expect_that!(normalize(Path::new("../outside.org")), none());
+    }
+
+    #[gtest]
+    fn relative_links() {
+        let (site, _) = crate::site::tests::site(&[
+            ("about.org", "#+title: About\n* Contact\n"),
+            ("b/post.org", "#+title: Post\n"),
+        ]);
+        let about = site.get(Path::new("about.org")).unwrap();
+        let post = site.get(Path::new("b/post.org")).unwrap();
+        let from = Path::new("b/other.org");
+
+        expect_that!(
+            resolve("../about.org", &site, from),
+            eq(&Target::Page {
+                page: about,
+                section: None
+            })
+        );
+        expect_that!(
+            resolve("file:/about.org::*Contact", &site, from),
+            eq(&Target::Page {
+                page: about,
+                section: Some(("contact", "Contact"))
+            })
+        );
+        expect_that!(
+            resolve("post.org::*Missing", &site, from),
+            eq(&Target::Page {
+                page: post,
+                section: None
+            })
+        );
+        expect_that!(
+            resolve("./other/data.csv", &site, from),
+            eq(&Target::Asset(String::from("/b/other/data.csv")))
+        );
+        expect_that!(resolve("file:draft.org", &site, from), eq(&Target::Broken));
+        expect_that!(
+            resolve("https://example.com/a.org", &site, from),
+            eq(&Target::External)
+        );
+    }
+}
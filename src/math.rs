@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use orgize::Org;
+use orgize::TextRange;
+use orgize::ast::{LatexEnvironment, LatexFragment};
+use orgize::export::HtmlEscape;
+use orgize::rowan::ast::AstNode;
+use pulldown_latex::config::DisplayMode;
+use pulldown_latex::{Parser, RenderConfig, Storage, push_mathml};
+use regex::Regex;
+
+/// Environments getting an equation number, their starred versions don't
+const NUMBERED_ENVIRONMENTS: &[&str] = &["equation", "align", "gather", "multline"];
+
+/// `\label{name}`, taken out of the equation to become its id
+static LABEL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\\label\{([^{}]+)\}").expect("Valid regex"));
+
+/// A tag written by the MathML renderer, like `<mo stretchy="true">` or `</mo>`
+static TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^</?[a-zA-Z][a-zA-Z0-9]*(?:\s+[a-zA-Z-]+="[^"]*")*\s*/?>"#).expect("Valid regex")
+});
+
+/// An entity, like `&nbsp;` or `&#x2061;`
+static ENTITY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^&(?:[a-zA-Z]+|#[0-9]+|#x[0-9a-fA-F]+);").expect("Valid regex"));
+
+/// `\name` of a `\begin{name}` environment
+static ENVIRONMENT_NAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*\\begin\{([^{}]+)\}").expect("Valid regex"));
+
+/// A `\begin{equation}`-like environment
+#[derive(Debug)]
+struct Equation {
+    label: Option<String>,
+    number: Option<usize>,
+}
+
+/// Display equations of a page, numbered in document order.
+///
+/// A `\label{name}` on an equation makes it the target of `\ref{name}`,
+/// `\eqref{name}` and `[[name]]` links.
+#[derive(Debug, Default)]
+pub struct Equations {
+    /// By the position of their environment on the document
+    equations: HashMap<TextRange, Equation>,
+    /// `(label, number)` for labels of numbered equations
+    numbers: HashMap<String, usize>,
+}
+
+impl Equations {
+    pub fn new(doc: &Org) -> Self {
+        let mut equations = Self::default();
+        let mut number = 0;
+        for environment in doc
+            .document()
+            .syntax()
+            .descendants()
+            .filter_map(LatexEnvironment::cast)
+        {
+            let raw = environment.raw();
+            let label = LABEL.captures(&raw).map(|c| c[1].trim().to_string());
+            let numbered = ENVIRONMENT_NAME
+                .captures(&raw)
+                .is_some_and(|c| NUMBERED_ENVIRONMENTS.contains(&&c[1]));
+
+            let equation = Equation {
+                number: numbered.then(|| {
+                    number += 1;
+                    number
+                }),
+                label,
+            };
+            if let (Some(label), Some(number)) = (&equation.label, equation.number) {
+                equations.numbers.insert(label.clone(), number);
+            }
+            equations
+                .equations
+                .insert(environment.text_range(), equation);
+        }
+        equations
+    }
+
+    /// Number of the equation labelled `label`, if any
+    pub fn number(&self, label: &str) -> Option<usize> {
+        self.numbers.get(label).copied()
+    }
+
+    /// Renders an environment as a display equation, with its number on the side.
+    pub fn environment(&self, environment: &LatexEnvironment, file_rel_path: &Path) -> String {
+        let raw = environment.raw();
+        let latex = LABEL.replace_all(raw.trim(), "");
+        let math = to_mathml(&latex, DisplayMode::Block, file_rel_path);
+
+        match self.equations.get(&environment.text_range()) {
+            Some(Equation {
+                label: Some(label),
+                number,
+            }) => format!(
+                r#"<div class="equation" id="{}">{math}{}</div>"#,
+                HtmlEscape(label),
+                number_html(*number),
+            ),
+            Some(Equation {
+                label: None,
+                number,
+            }) => format!(
+                r#"<div class="equation">{math}{}</div>"#,
+                number_html(*number)
+            ),
+            None => format!(r#"<div class="equation">{math}</div>"#),
+        }
+    }
+
+    /// Renders `$…$`, `$$…$$`, `\(…\)` and `\[…\]` fragments as MathML, and
+    /// `\ref{label}` or `\eqref{label}` as links to their equation.
+    ///
+    /// Other fragments, like `\command{…}`, are kept as text.
+    pub fn fragment(&self, fragment: &LatexFragment, file_rel_path: &Path) -> String {
+        let raw = fragment.raw();
+        if let Some((command, label)) = raw
+            .strip_prefix('\\')
+            .and_then(|r| r.strip_suffix('}'))
+            .and_then(|r| r.split_once('{'))
+            && (command == "ref" || command == "eqref")
+        {
+            let Some(number) = self.number(label) else {
+                log::warn!(
+                    "{}: Can't find the equation referenced by '{raw}'",
+                    file_rel_path.display()
+                );
+                return HtmlEscape(&raw).to_string();
+            };
+            return format!(
+                r##"<a href="#{}" class="equation-ref">{}</a>"##,
+                HtmlEscape(label),
+                if command == "eqref" {
+                    format!("({number})")
+                } else {
+                    number.to_string()
+                }
+            );
+        }
+
+        fragment_to_html(&raw, file_rel_path)
+    }
+}
+
+/// Renders a math fragment on its own, without equation references.
+pub fn fragment_to_html(raw: &str, file_rel_path: &Path) -> String {
+    let delimited = |start: &str, end: &str| {
+        raw.strip_prefix(start)
+            .and_then(|r| r.strip_suffix(end))
+            .filter(|latex| !latex.is_empty())
+    };
+
+    if let Some(latex) = delimited("$$", "$$").or_else(|| delimited(r"\[", r"\]")) {
+        to_mathml(latex, DisplayMode::Block, file_rel_path)
+    } else if let Some(latex) = delimited("$", "$").or_else(|| delimited(r"\(", r"\)")) {
+        to_mathml(latex, DisplayMode::Inline, file_rel_path)
+    } else {
+        HtmlEscape(raw).to_string()
+    }
+}
+
+fn number_html(number: Option<usize>) -> String {
+    number
+        .map(|n| format!(r#"<span class="equation-number">({n})</span>"#))
+        .unwrap_or_default()
+}
+
+/// Converts LaTeX math to a `<math>` element. Invalid math is logged and kept
+/// as `<code>`, so it still reads as the source.
+fn to_mathml(latex: &str, display_mode: DisplayMode, file_rel_path: &Path) -> String {
+    let storage = Storage::new();
+    if let Some(error) = Parser::new(latex, &storage).find_map(|e| e.err()) {
+        log::warn!(
+            "{}: Can't render math '{}': {}",
+            file_rel_path.display(),
+            latex.trim(),
+            error.to_string().lines().next().unwrap_or_default()
+        );
+        return format!(r#"<code class="math-error">{}</code>"#, HtmlEscape(latex));
+    }
+
+    let storage = Storage::new();
+    let mut mathml = String::new();
+    let config = RenderConfig {
+        display_mode,
+        // For feed readers
+        xml: true,
+        ..Default::default()
+    };
+    if let Err(error) = push_mathml(&mut mathml, Parser::new(latex, &storage), config) {
+        log::error!(
+            "{}: Can't render math '{}': {error}",
+            file_rel_path.display(),
+            latex.trim()
+        );
+        return format!(r#"<code class="math-error">{}</code>"#, HtmlEscape(latex));
+    }
+    escape_text(&mathml)
+}
+
+/// Escapes the `<`, `>` and `&` of the text between the tags of `mathml`.
+///
+/// Operators and identifiers are written unescaped, like `<mo><</mo>`.
+fn escape_text(mathml: &str) -> String {
+    let mut escaped = String::with_capacity(mathml.len());
+    let mut rest = mathml;
+    while let Some(c) = rest.chars().next() {
+        let markup = match c {
+            '<' => TAG.find(rest),
+            '&' => ENTITY.find(rest),
+            _ => None,
+        };
+        if let Some(markup) = markup {
+            escaped.push_str(markup.as_str());
+            rest = &rest[markup.end()..];
+            continue;
+        }
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            c => escaped.push(c),
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use indoc::indoc;
+
+    #[gtest]
+    fn fragments() {
+        let path = Path::new("math.org");
+        expect_that!(
+            fragment_to_html("$a < b$", path),
+            eq(
+                r#"<math display="inline" xmlns="http://www.w3.org/1998/Math/MathML"><mi>a</mi><mo>&lt;</mo><mi>b</mi></math>"#
+            )
+        );
+        expect_that!(
+            fragment_to_html(r"\(x^2\)", path),
+            eq(
+                "<math display=\"inline\" xmlns=\"http://www.w3.org/1998/Math/MathML\"><msup><mi>x</mi><mn>2</mn></msup></math>"
+            )
+        );
+        expect_that!(
+            fragment_to_html(r"\[x\]", path),
+            eq(
+                r#"<math display="block" xmlns="http://www.w3.org/1998/Math/MathML"><mi>x</mi></math>"#
+            )
+        );
+        expect_that!(fragment_to_html(r"\frac{1", path), eq(r"\frac{1"));
+        expect_that!(
+            fragment_to_html(r"$\frac{1$", path),
+            eq(r#"<code class="math-error">\frac{1</code>"#)
+        );
+    }
+
+    #[gtest]
+    fn escaped_text() {
+        expect_that!(
+            escape_text(
+                r#"<mo stretchy="true">></mo><mi>a<b&c</mi><mtext>&nbsp;&lt;</mtext><mspace width="1em" />"#
+            ),
+            eq(
+                r#"<mo stretchy="true">&gt;</mo><mi>a&lt;b&amp;c</mi><mtext>&nbsp;&lt;</mtext><mspace width="1em" />"#
+            )
+        );
+        expect_that!(
+            fragment_to_html(r"$x > \operatorname{a<b}$", Path::new("math.org")),
+            all![
+                contains_substring("<mo>&gt;</mo>"),
+                contains_substring("a&lt;b</mi>"),
+            ]
+        );
+    }
+
+    #[gtest]
+    fn numbered_and_referenced() {
+        let doc = Org::parse(indoc! {r###"
+          \begin{equation}
+          E = mc^2 \label{eq:energy}
+          \end{equation}
+
+          \begin{equation*}
+          x
+          \end{equation*}
+
+          \begin{align}
+          a &= b
+          \end{align}
+        "###});
+        let equations = Equations::new(&doc);
+        let path = Path::new("math.org");
+
+        let environments: Vec<LatexEnvironment> = doc
+            .document()
+            .syntax()
+            .descendants()
+            .filter_map(LatexEnvironment::cast)
+            .collect();
+        expect_that!(
+            equations.environment(&environments[0], path),
+            all![
+                starts_with(
+                    r#"<div class="equation" id="eq:energy"><math display="block" xmlns="http://www.w3.org/1998/Math/MathML">"#
+                ),
+                ends_with(r#"</math><span class="equation-number">(1)</span></div>"#),
+                not(contains_substring("label")),
+            ]
+        );
+        expect_that!(
+            equations.environment(&environments[1], path),
+            not(contains_substring("equation-number"))
+        );
+        expect_that!(
+            equations.environment(&environments[2], path),
+            contains_substring(r#"<span class="equation-number">(2)</span>"#)
+        );
+        expect_that!(equations.number("eq:energy"), some(eq(1)));
+    }
+}
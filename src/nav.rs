@@ -0,0 +1,237 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use orgize::export::HtmlEscape;
+
+use crate::page;
+use crate::site;
+
+/// Most related pages to list
+const MAX_RELATED: usize = 5;
+
+/// Renders the navigation closing a page: its series, the previous and next
+/// posts on the same directory, then related pages under a `depth` heading.
+///
+/// Pages that aren't posts, part of a series nor share tags get nothing.
+pub fn to_html(site: &site::Site, rel_path: &Path, depth: i8) -> String {
+    let Some(current) = site.get(rel_path) else {
+        return String::new();
+    };
+
+    let mut html = String::new();
+    if let Some(series) = &current.series {
+        html.push_str(&series_html(site, current, series));
+    }
+    if current.post {
+        html.push_str(&post_nav_html(site, current));
+    }
+    html.push_str(&related_html(site, current, depth));
+    html
+}
+
+/// `Part N of M` followed by every part, the current one unlinked.
+fn series_html(site: &site::Site, current: &site::Page, series: &str) -> String {
+    let parts = site.series(series);
+    let Some(n) = parts.iter().position(|p| p.rel_path == current.rel_path) else {
+        return String::new();
+    };
+
+    let mut html = format!(
+        r#"<nav class="series"><p>Part {} of {} of <strong>{}</strong></p><ol>"#,
+        n + 1,
+        parts.len(),
+        HtmlEscape(series),
+    );
+    for (i, p) in parts.iter().enumerate() {
+        if i == n {
+            let _ = write!(
+                html,
+                r#"<li aria-current="page">{}</li>"#,
+                page::title_html(&p.title)
+            );
+        } else {
+            let _ = write!(
+                html,
+                "<li>{}{}</a></li>",
+                page::local_link(HtmlEscape(p.path())),
+                page::title_html(&p.title),
+            );
+        }
+    }
+    html.push_str("</ol></nav>");
+    html
+}
+
+/// Links to the posts published right before and after this one.
+fn post_nav_html(site: &site::Site, current: &site::Page) -> String {
+    let posts = site.posts_beside(&current.rel_path);
+    let Some(n) = posts.iter().position(|p| p.rel_path == current.rel_path) else {
+        return String::new();
+    };
+    let previous = n.checked_sub(1).map(|i| posts[i]);
+    let next = posts.get(n + 1);
+    if previous.is_none() && next.is_none() {
+        return String::new();
+    }
+
+    let mut html = String::from(r#"<nav class="post-nav">"#);
+    if let Some(p) = previous {
+        let _ = write!(
+            html,
+            r#"<p class="previous">Previous: {}{}</a></p>"#,
+            page::local_link(HtmlEscape(p.path())),
+            page::title_html(&p.title),
+        );
+    }
+    if let Some(p) = next {
+        let _ = write!(
+            html,
+            r#"<p class="next">Next: {}{}</a></p>"#,
+            page::local_link(HtmlEscape(p.path())),
+            page::title_html(&p.title),
+        );
+    }
+    html.push_str("</nav>");
+    html
+}
+
+/// Pages sharing tags with this one, see [`site::Site::related`].
+fn related_html(site: &site::Site, current: &site::Page, depth: i8) -> String {
+    let related = site.related(&current.rel_path, MAX_RELATED);
+    if related.is_empty() {
+        return String::new();
+    }
+
+    let heading = page::heading(depth);
+    let mut html = format!(r#"<section class="related"><{heading}>Related</{heading}><ul>"#);
+    for p in related {
+        let _ = write!(
+            html,
+            "<li>{}{}</a></li>",
+            page::local_link(HtmlEscape(p.path())),
+            page::title_html(&p.title),
+        );
+    }
+    html.push_str("</ul></section>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use orgize::Org;
+
+    fn page(rel_path: &str, properties: &str) -> site::Page {
+        tagged(rel_path, properties, &[])
+    }
+
+    fn tagged(rel_path: &str, properties: &str, tags: &[&str]) -> site::Page {
+        let doc = Org::parse(format!(
+            ":PROPERTIES:\n{properties}:END:\n#+title: {rel_path}\n"
+        ));
+        let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+        site::Page::new(&doc, &tags, Path::new(rel_path))
+    }
+
+    #[gtest]
+    fn previous_and_next() {
+        let mut site = site::Site::new();
+        site.push(page("b.org", ":created_time: 2025-01-01\n"));
+        site.push(page("b/a.org", ":publication_date: 2025-01-10\n"));
+        site.push(page("b/c.org", ":publication_date: 2025-01-30\n"));
+        site.push(page("b/b.org", ":publication_date: 2025-01-20\n"));
+        site.push(page(
+            "b/draft.org",
+            ":publication_date: 2025-01-25\n:Draft: t\n",
+        ));
+        site.push(page("b/old/d.org", ":publication_date: 2025-01-15\n"));
+
+        let middle = to_html(&site, Path::new("b/b.org"), 2);
+        expect_that!(
+            middle,
+            all![
+                starts_with(
+                    r#"<nav class="post-nav"><p class="previous">Previous: <a hx-get="/b/a/_.html""#
+                ),
+                contains_substring(r#"<p class="next">Next: <a hx-get="/b/c/_.html""#),
+                not(contains_substring("draft")),
+                not(contains_substring("/b/old/d")),
+            ]
+        );
+        expect_that!(
+            to_html(&site, Path::new("b/a.org"), 2),
+            all![
+                not(contains_substring("Previous")),
+                contains_substring("/b/b/_.html"),
+            ]
+        );
+        expect_that!(to_html(&site, Path::new("b/old/d.org"), 2), eq(""));
+        expect_that!(to_html(&site, Path::new("b.org"), 2), eq(""));
+    }
+
+    #[gtest]
+    fn series() {
+        let mut site = site::Site::new();
+        site.push(page(
+            "b/two.org",
+            ":publication_date: 2025-02-01\n:series: Org & Rust\n",
+        ));
+        site.push(page(
+            "b/one.org",
+            ":publication_date: 2025-01-01\n:series: Org & Rust\n",
+        ));
+        site.push(page("b/other.org", ":publication_date: 2025-01-15\n"));
+
+        expect_that!(
+            to_html(&site, Path::new("b/two.org"), 2),
+            all![
+                starts_with(
+                    r#"<nav class="series"><p>Part 2 of 2 of <strong>Org &amp; Rust</strong></p><ol><li><a hx-get="/b/one/_.html""#
+                ),
+                contains_substring(r#"<li aria-current="page">b/two.org</li></ol></nav>"#),
+                contains_substring(r#"<p class="previous">Previous: <a hx-get="/b/other/_.html""#),
+            ]
+        );
+    }
+
+    #[gtest]
+    fn related() {
+        let mut site = site::Site::new();
+        site.push(tagged("about.org", "", &["Rust", "Org", "Emacs"]));
+        site.push(tagged(
+            "b/one.org",
+            ":created_time: 2025-01-01\n",
+            &["Rust"],
+        ));
+        site.push(tagged(
+            "b/two.org",
+            ":created_time: 2025-02-01\n",
+            &["Rust"],
+        ));
+        site.push(tagged(
+            "b/both.org",
+            ":created_time: 2024-01-01\n",
+            &["Rust", "Org"],
+        ));
+        site.push(tagged("b/none.org", ":created_time: 2025-03-01\n", &["Go"]));
+        site.push(tagged("b/draft.org", ":Draft: t\n", &["Rust", "Org"]));
+
+        let html = to_html(&site, Path::new("about.org"), 2);
+        expect_that!(
+            html,
+            all![
+                starts_with(r#"<section class="related"><h2>Related</h2><ul><li>"#),
+                not(contains_substring("/b/none/")),
+                not(contains_substring("/b/draft/")),
+            ]
+        );
+        let both_at = html.find("/b/both/_.html").unwrap();
+        let two_at = html.find("/b/two/_.html").unwrap();
+        let one_at = html.find("/b/one/_.html").unwrap();
+        expect_that!(both_at, lt(two_at));
+        expect_that!(two_at, lt(one_at));
+
+        expect_that!(to_html(&site, Path::new("b/none.org"), 2), eq(""));
+    }
+}
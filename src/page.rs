@@ -5,10 +5,30 @@ use orgize::Org;
 use orgize::export::HtmlEscape;
 use orgize::export::HtmlExport;
 use orgize::export::{Container, Event, Traverser, from_fn_with_ctx};
-use slugify::slugify;
+use orgize::rowan::ast::AstNode;
+use orgize::{SyntaxKind, SyntaxNode};
+
+use crate::anchors::Anchors;
+use crate::archive;
+use crate::backlinks;
+use crate::code;
+use crate::figure;
+use crate::footnotes::Footnotes;
+use crate::links::{self, Target};
+use crate::math::Equations;
+use crate::nav;
+use crate::site::Site;
+use crate::table::Table;
+use crate::tags;
+use crate::toc;
 
 const HTML_HEADING_LEVELS: [&str; 6] = ["h1", "h2", "h3", "h4", "h5", "h6"];
 
+/// HTML heading for a depth, `h1` to `h6`
+pub fn heading(depth: i8) -> &'static str {
+    HTML_HEADING_LEVELS[(depth - 1).clamp(0, 5) as usize]
+}
+
 pub fn org_tags(_doc: &Org, contents: &str) -> Vec<String> {
     let mut tags = vec![];
 
@@ -32,16 +52,161 @@ pub fn org_tags(_doc: &Org, contents: &str) -> Vec<String> {
     tags
 }
 
-#[derive(Debug, Default)]
-struct PageRequirements {
-    has_code: bool,
+/// Reads a date from the document properties.
+///
+/// Unparseable dates are reported and ignored.
+pub fn org_date(
+    doc: &Org,
+    key: &str,
+    file_rel_path: &Path,
+) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    let value = doc.document().properties()?.get(key)?;
+    let date = parse_date(&value);
+    if date.is_none() {
+        log::error!(
+            "{}: Can't parse {key} '{value}'. Use RFC 2822, ISO 8601 or an org timestamp.",
+            file_rel_path.display()
+        );
+    }
+    date
+}
+
+/// Parses a date in any of the formats we find in the wild.
+///
+/// - RFC 2822, `Wed, 22 Jan 2025 15:05 PDT`
+/// - ISO 8601, `2025-01-22T16:00:00-03:00`, `2025-01-22T16:00` or `2025-01-22`
+/// - Org timestamps, `<2025-01-22 Wed 16:00>` or `[2025-01-22 Wed]`
+///
+/// Dates without an offset are assumed to be in local time.
+pub fn parse_date(date: &str) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+
+    let date = date.trim();
+    if let Ok(date) = chrono::DateTime::parse_from_rfc3339(date) {
+        return Some(date);
+    }
+    if let Ok(date) = chrono::DateTime::parse_from_rfc2822(date) {
+        return Some(date);
+    }
+
+    let naive = if let Some(timestamp) = date
+        .strip_prefix('<')
+        .and_then(|d| d.strip_suffix('>'))
+        .or_else(|| date.strip_prefix('[').and_then(|d| d.strip_suffix(']')))
+    {
+        // <DATE DAY TIME[-END] REPEATER>
+        let mut parts = timestamp.split_whitespace();
+        let day = NaiveDate::parse_from_str(parts.next()?, "%Y-%m-%d").ok()?;
+        let time = parts
+            .find_map(|p| NaiveTime::parse_from_str(p.split('-').next()?, "%H:%M").ok())
+            .unwrap_or_default();
+        day.and_time(time)
+    } else {
+        NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S")
+            .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M"))
+            .or_else(|_| {
+                NaiveDate::parse_from_str(date, "%Y-%m-%d").map(|d| d.and_time(NaiveTime::MIN))
+            })
+            .ok()?
+    };
+
+    chrono::Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|d| d.fixed_offset())
+}
+
+/// Value of an affiliated keyword of an element, like `#+caption:`.
+///
+/// Lowercase ones are parsed as keywords right before the element, so those
+/// are looked up too.
+pub fn affiliated_keyword(node: &SyntaxNode, key: &str) -> Option<String> {
+    use orgize::ast::{AffiliatedKeyword, Keyword};
+    node.children()
+        .take_while(|n| n.kind() == SyntaxKind::AFFILIATED_KEYWORD)
+        .filter_map(AffiliatedKeyword::cast)
+        .find(|k| k.key().eq_ignore_ascii_case(key))
+        .and_then(|k| k.value().map(|v| v.trim().to_string()))
+        .or_else(|| {
+            node.siblings(orgize::rowan::Direction::Prev)
+                .skip(1)
+                .map_while(Keyword::cast)
+                .find(|k| k.key().eq_ignore_ascii_case(key))
+                .map(|k| k.value().trim().to_string())
+        })
+}
+
+/// Plain text title of a headline
+pub fn headline_title(headline: &orgize::ast::Headline) -> String {
+    headline.title().map(|e| e.to_string()).collect()
+}
+
+/// Renders an org title, like `#+title: Blogging with ~org-mode~`, as inline HTML.
+pub fn title_html(title: &str) -> String {
+    // Parse title as Org document
+    let title = Org::parse(title);
+    let Some(title) = title.first_node::<orgize::ast::Paragraph>() else {
+        return String::new();
+    };
+
+    let mut html = HtmlExport::default();
+    html.render(title.syntax());
+    let title_html = html.finish();
+    // Drop surrounding <p>...</p>
+    title_html[3..title_html.len() - 3 - 1].to_string()
+}
+
+/// Reverts the escaping of [`HtmlEscape`], or of any XML entity, on an
+/// attribute value. Values with unknown entities are kept as they are.
+pub fn unescape(value: &str) -> std::borrow::Cow<'_, str> {
+    quick_xml::escape::unescape(value).unwrap_or(value.into())
+}
+
+/// Opens an `<a>` that swaps the `_.html` fragment at `path` into `#content`.
+///
+/// `path` points to the page directory, without the trailing `/`, and must be
+/// escaped already.
+pub fn local_link(path: impl std::fmt::Display) -> String {
+    format!(
+        indoc! {r###"
+            <a hx-get="{0}/_.html"
+              preload
+              hx-target="#content"
+              hx-push-url="{0}/"
+              hx-history-target="{0}/"
+              aria-controls="content"
+              href="{0}" />
+        "###},
+        path
+    )
 }
 
-pub fn to_html(doc: Org, tags: &[String], file_rel_path: &Path) -> Result<String, std::io::Error> {
+/// Like [`local_link`], scrolling to the section `anchor` once swapped in.
+///
+/// `anchor` must be escaped already too.
+pub fn local_section_link(path: impl std::fmt::Display, anchor: impl std::fmt::Display) -> String {
+    format!(
+        indoc! {r###"
+            <a hx-get="{0}/_.html"
+              preload
+              hx-target="#content"
+              hx-swap="innerHTML show:[id='{1}']:top"
+              hx-push-url="{0}/#{1}"
+              hx-history-target="{0}/"
+              aria-controls="content"
+              href="{0}/#{1}" />
+        "###},
+        path, anchor
+    )
+}
+
+pub fn to_html(
+    doc: &Org,
+    tags: &[String],
+    file_rel_path: &Path,
+    site: &Site,
+) -> Result<String, std::io::Error> {
     let mut html_export = HtmlExport::default();
-    let file_name = file_rel_path.file_name().unwrap().to_str().unwrap();
-    let file_stem = file_name.trim_end_matches(".org");
-    let mut requirements = PageRequirements::default();
 
     assert!(file_rel_path.is_relative());
 
@@ -51,25 +216,44 @@ pub fn to_html(doc: Org, tags: &[String], file_rel_path: &Path) -> Result<String
     {
         base_depth = base_depth_token.as_ref().parse::<i8>().unwrap_or(0);
     }
+    // Depth of the enclosing headings, the title first
+    let mut depths = vec![base_depth];
+
+    // Without a `#+TOC:` keyword, the table of contents goes right after the title
+    let top_toc_depth = toc::depth(doc).filter(|_| !toc::has_keyword(doc));
+
+    let archive_html = |options: &str, depth: i8| {
+        let options = archive::Options::parse(options, file_rel_path);
+        archive::Archive::new(site, file_rel_path, options).to_html(1, depth)
+    };
+
+    // Last line number of a numbered source block, for `+n`
+    let mut last_line = 0;
+    // Results of the previous source block, when it doesn't export them
+    let mut hidden_results = None;
+    let mut footnotes = Footnotes::new(doc, file_rel_path);
+    let anchors = Anchors::new(doc);
+    let equations = Equations::new(doc);
+    let mut table: Option<Table> = None;
 
     let mut handler = from_fn_with_ctx(|event, ctx| {
         match event {
+            Event::Enter(container)
+                if hidden_results.is_some()
+                    && code::results_syntax(&container).map(|n| n.text_range())
+                        == hidden_results =>
+            {
+                hidden_results = None;
+                ctx.skip();
+            }
+
             Event::Enter(Container::Document(_doc)) => {
                 // Add title if present
                 if let Some(title) = doc.title() {
-                    // Parse title as Org document
-                    let title = Org::parse(title);
-                    let title = title.first_node::<orgize::ast::Paragraph>().unwrap();
-
-                    use orgize::rowan::ast::AstNode;
-                    let mut html = HtmlExport::default();
-                    html.render(title.syntax());
-                    let title_html = html.finish();
-                    // Drop surrounding <p>...</p>
-                    let title_html = &title_html[3..title_html.len() - 3 - 1];
+                    let title_html = title_html(&title);
 
                     let depth = base_depth;
-                    let heading = HTML_HEADING_LEVELS[(depth - 1).clamp(0, 5) as usize];
+                    let heading = heading(depth);
 
                     if tags.is_empty() {
                         // <H*>$TITLE</H*>
@@ -77,106 +261,195 @@ pub fn to_html(doc: Org, tags: &[String], file_rel_path: &Path) -> Result<String
                     } else {
                         // <hgroup>
                         //   <H*>$TITLE</H*>
-                        //   <p>Tags: <dd-tag>TAG</dd-tag>...</p>
+                        //   <p>Tags: <dd-tag><a href="/tags/TAG">TAG</a></dd-tag>...</p>
                         // </hgroup>
                         html_export.push_str(format!(
                             r#"<hgroup><{heading}>{title_html}</{heading}><p>Tags:"#
                         ));
                         for t in tags {
-                            html_export.push_str(format!(r#" <dd-tag>{t}</dd-tag>"#));
+                            html_export.push_str(format!(
+                                r#" <dd-tag>{}{}</a></dd-tag>"#,
+                                local_link(HtmlEscape(tags::tag_path(t))),
+                                HtmlEscape(t)
+                            ));
                         }
                         html_export.push_str(r#"</p></hgroup>"#);
                     }
                 }
+
+                if let Some(toc_depth) = top_toc_depth {
+                    html_export.push_str(toc::to_html(doc, &anchors, toc_depth, base_depth + 1));
+                }
             }
             Event::Leave(Container::Document(_doc)) => {
-                if requirements.has_code {
-                    html_export.push_str("<script src=\"https://cdn.jsdelivr.net/npm/@arborium/arborium@1/dist/arborium.iife.js\" data-theme=\"ayu-dark\"></script>");
-                }
+                html_export.push_str(footnotes.to_html(depths[0] + 1));
+                html_export.push_str(backlinks::to_html(site, file_rel_path, depths[0] + 1));
+                html_export.push_str(nav::to_html(site, file_rel_path, depths[0] + 1));
+            }
+
+            Event::Enter(Container::FnRef(reference)) => {
+                ctx.skip();
+                html_export.push_str(footnotes.reference(&reference));
+            }
+            Event::Enter(Container::FnDef(_)) => {
+                // Rendered on the notes section
+                ctx.skip();
+            }
+            Event::Enter(Container::Paragraph(paragraph))
+                if footnotes.is_continuation(paragraph.syntax()) =>
+            {
+                ctx.skip();
+            }
+
+            Event::Enter(Container::Paragraph(paragraph))
+                if figure::standalone_image(&paragraph).is_some() =>
+            {
+                ctx.skip();
+                let link = figure::standalone_image(&paragraph).unwrap();
+                html_export.push_str(figure::to_html(&paragraph, &link, site, file_rel_path));
             }
 
+            Event::Enter(Container::Link(link)) if link.is_image() => {
+                ctx.skip();
+                html_export.push_str(figure::img_html(&link, None, site, file_rel_path));
+            }
             Event::Enter(Container::Link(link)) => {
                 let path = link.path();
-                let mut path: &str = path.trim_start_matches("file:");
+                let path: &str = path.trim_start_matches("file:");
                 log::debug!("Linking to: {path:?}");
 
-                let local_link_prefix = format!("./{file_stem}/");
-
-                // Handle local links
-                let is_local_org_link = path.ends_with(".org");
-                let is_local_link = path.starts_with(&local_link_prefix);
-                if is_local_link {
-                    path = path.strip_prefix(&local_link_prefix).unwrap();
-
-                    if path.ends_with(".org") {
-                        path = path.strip_suffix(".org").unwrap();
+                // Sections on this page
+                if let Some((anchor, title)) = anchors.resolve(path) {
+                    html_export.push_str(format!(r##"<a href="#{}">"##, HtmlEscape(anchor)));
+                    if !link.has_description() {
+                        html_export.push_str(format!("{}</a>", title_html(title)));
+                        ctx.skip();
                     }
+                    return;
                 }
-
-                let target = HtmlEscape(&path);
-
-                if link.is_image() {
-                    html_export.push_str(format!(r#"<img src="{}">"#, target));
-                    return ctx.skip();
+                // Labelled equations
+                if let Some(number) = equations.number(path) {
+                    html_export.push_str(format!(
+                        r##"<a href="#{}" class="equation-ref">"##,
+                        HtmlEscape(path)
+                    ));
+                    if !link.has_description() {
+                        html_export.push_str(format!("({number})</a>"));
+                        ctx.skip();
+                    }
+                    return;
+                }
+                if path.starts_with('#') || path.starts_with('*') {
+                    log::warn!(
+                        "{}: Can't find the section linked by '{path}'",
+                        file_rel_path.display()
+                    );
                 }
 
-                html_export.push_str(if is_local_link {
-                    if is_local_org_link {
+                let (html, text) = match links::resolve(path, site, file_rel_path) {
+                    Target::Page {
+                        page,
+                        section: Some((anchor, title)),
+                    } => (
+                        if page.rel_path == file_rel_path {
+                            format!(r##"<a href="#{}">"##, HtmlEscape(anchor))
+                        } else {
+                            local_section_link(HtmlEscape(page.path()), HtmlEscape(anchor))
+                        },
+                        title_html(title),
+                    ),
+                    Target::Page {
+                        page,
+                        section: None,
+                    } => (local_link(HtmlEscape(page.path())), title_html(&page.title)),
+                    Target::Asset(src) => (
                         format!(
                             indoc! {r###"
-                                <a hx-get="{0}/_.html"
+                                <a target="blank"
                                   preload
-                                  hx-target="#content"
-                                  hx-push-url="{0}/"
-                                  hx-history-target="{0}/"
-                                  aria-controls="content"
                                   href="{0}" />
-                        "###},
-                            target
-                        )
-                    } else {
-                        // Not a local .org link
+                            "###},
+                            HtmlEscape(&src)
+                        ),
+                        HtmlEscape(path).to_string(),
+                    ),
+                    Target::Broken => (
+                        String::from(r#"<a class="broken-link">"#),
+                        HtmlEscape(path).to_string(),
+                    ),
+                    Target::External => (
                         format!(
                             indoc! {r###"
-                                <a target="blank"
-                                  preload
-                                  href="{0}" />
-                        "###},
-                            target
-                        )
-                    }
-                } else {
-                    format!(
-                        indoc! {r###"
                                 <a href="{}"
                                   preload
                                   target="_blank" />
-                        "###},
-                        target
-                    )
-                });
-
+                            "###},
+                            HtmlEscape(path)
+                        ),
+                        HtmlEscape(path).to_string(),
+                    ),
+                };
+                html_export.push_str(html);
                 if !link.has_description() {
-                    html_export.push_str(format!("{}</a>", target.0.trim()));
+                    html_export.push_str(format!("{text}</a>"));
                     ctx.skip();
                 }
             }
 
-            Event::Enter(Container::SourceBlock(block)) => {
-                requirements.has_code = true;
+            Event::LatexFragment(fragment) => {
+                html_export.push_str(equations.fragment(&fragment, file_rel_path));
+            }
+            Event::LatexEnvironment(environment) => {
+                html_export.push_str(equations.environment(&environment, file_rel_path));
+            }
 
-                // FIXME: Avoid weird prefix spacing? Check https://docs.rs/indoc
-                if let Some(language) = block.language() {
-                    html_export.push_str(format!(
-                        r#"<pre><code class="language-{}">"#,
-                        HtmlEscape(&language)
-                    ));
-                } else {
-                    html_export.push_str("<pre><code>");
+            Event::Enter(Container::OrgTable(org_table)) => {
+                let (new_table, html) = Table::new(&org_table);
+                table = Some(new_table);
+                html_export.push_str(html);
+            }
+            Event::Leave(Container::OrgTable(_)) => {
+                if let Some(mut table) = table.take() {
+                    html_export.push_str(table.close());
+                }
+            }
+            Event::Enter(Container::OrgTableRow(row)) => {
+                if let Some(table) = &mut table {
+                    html_export.push_str(table.enter_row(&row));
+                    if !table.has_cells(&row) {
+                        ctx.skip();
+                    }
+                }
+            }
+            Event::Leave(Container::OrgTableRow(_)) => {
+                if let Some(table) = &mut table {
+                    html_export.push_str(table.leave_row());
                 }
             }
-            Event::Leave(Container::SourceBlock(_block)) => {
-                html_export.push_str("</code></pre>");
+            Event::Enter(Container::OrgTableCell(cell)) => {
+                if let Some(table) = &mut table {
+                    html_export.push_str(table.enter_cell(&cell));
+                }
+            }
+            Event::Leave(Container::OrgTableCell(_)) => {
+                if let Some(table) = &mut table {
+                    html_export.push_str(table.leave_cell());
+                }
+            }
+
+            Event::Enter(Container::SourceBlock(block)) => {
+                ctx.skip();
+
+                let options = code::Options::new(&block, last_line, file_rel_path);
+                if let Some(last) = options.last_line(block.value().lines().count()) {
+                    last_line = last;
+                }
+                if !options.exports.results() {
+                    hidden_results = code::results(&block).map(|n| n.text_range());
+                }
+                if options.exports.code() {
+                    html_export.push_str(code::to_html(&block, &options, file_rel_path));
+                }
             }
 
             Event::Enter(Container::FixedWidth(fixed)) => {
@@ -217,6 +490,9 @@ pub fn to_html(doc: Org, tags: &[String], file_rel_path: &Path) -> Result<String
                                 modified_time,
                             ));
                         }
+                        (archive::GENERATE_INDEX, options) => {
+                            html_export.push_str(archive_html(options, depths.last().unwrap() + 1));
+                        }
                         (k, v) => {
                             log::debug!("Ignoring property {}:{}", k, v);
                         }
@@ -224,11 +500,37 @@ pub fn to_html(doc: Org, tags: &[String], file_rel_path: &Path) -> Result<String
                 }
             }
 
+            Event::Enter(Container::Keyword(keyword))
+                if keyword.key().eq_ignore_ascii_case(toc::TOC_KEYWORD) =>
+            {
+                ctx.skip();
+                match toc::keyword_depth(&keyword.value()) {
+                    Some(toc_depth) => html_export.push_str(toc::to_html(
+                        doc,
+                        &anchors,
+                        toc_depth,
+                        depths.last().unwrap() + 1,
+                    )),
+                    None => log::warn!(
+                        "{}: Ignoring unsupported '#+TOC: {}'",
+                        file_rel_path.display(),
+                        keyword.value().trim()
+                    ),
+                }
+            }
+
+            Event::Enter(Container::Keyword(keyword))
+                if keyword.key().eq_ignore_ascii_case(archive::GENERATE_INDEX) =>
+            {
+                ctx.skip();
+                html_export.push_str(archive_html(&keyword.value(), depths.last().unwrap() + 1));
+            }
+
             Event::Enter(Container::Headline(headline)) => {
                 let depth = (headline.level() as i8) + base_depth;
-                let heading = HTML_HEADING_LEVELS[(depth - 1).clamp(0, 5) as usize];
-                let title = headline.title().map(|e| e.to_string()).collect::<String>();
-                let slug = slugify!(&title);
+                let heading = heading(depth);
+                let title = headline_title(&headline);
+                let slug = HtmlEscape(anchors.get(&headline).unwrap_or_default());
 
                 if title.starts_with(".") {
                     base_depth -= 1;
@@ -239,6 +541,8 @@ pub fn to_html(doc: Org, tags: &[String], file_rel_path: &Path) -> Result<String
                         title.replace(".", " ").trim()
                     ));
                 } else {
+                    depths.push(depth);
+
                     // <section id="$SLUG(TITLE)">
                     html_export.push_str(format!(
                         "<section id=\"{}\" class=\"s{}\">",
@@ -273,11 +577,13 @@ pub fn to_html(doc: Org, tags: &[String], file_rel_path: &Path) -> Result<String
                 }
             }
             Event::Leave(Container::Headline(headline)) => {
-                let title = headline.title().map(|e| e.to_string()).collect::<String>();
+                let title = headline_title(&headline);
                 if title.starts_with(".") {
                     base_depth += 1;
                     html_export.push_str("</div>");
                 } else {
+                    depths.pop();
+
                     // </section>
                     html_export.push_str("</section>");
                 }
@@ -298,6 +604,7 @@ pub fn to_html(doc: Org, tags: &[String], file_rel_path: &Path) -> Result<String
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
     use googletest::prelude::*;
 
     #[gtest]
@@ -311,7 +618,7 @@ mod tests {
         let tags = org_tags(&doc, contents);
 
         let rel_path = Path::new("simple_doc.org");
-        let html = to_html(doc, &tags, rel_path);
+        let html = to_html(&doc, &tags, rel_path, &Site::default());
 
         expect_that!(
             html,
@@ -321,6 +628,41 @@ mod tests {
         );
     }
 
+    #[gtest]
+    fn dates() {
+        let rfc_2822 = parse_date("Wed, 22 Jan 2025 15:05 PDT");
+        expect_that!(
+            rfc_2822.map(|d| d.to_rfc3339()),
+            some(eq("2025-01-22T15:05:00-07:00"))
+        );
+
+        let iso_8601 = parse_date("2025-01-22T16:00:00-03:00");
+        expect_that!(
+            iso_8601.map(|d| d.to_rfc3339()),
+            some(eq("2025-01-22T16:00:00-03:00"))
+        );
+
+        // The offset on that day, which isn't today's one under DST
+        let local = chrono::Local
+            .with_ymd_and_hms(2025, 1, 22, 16, 0, 0)
+            .earliest()
+            .map(|d| d.fixed_offset());
+        let active = parse_date("<2025-01-22 Wed 16:00 +1w>");
+        expect_that!(
+            active.map(|d| d.naive_local().to_string()),
+            some(eq("2025-01-22 16:00:00"))
+        );
+        expect_that!(active, eq(local));
+        let inactive = parse_date("[2025-01-22 Wed]");
+        expect_that!(
+            inactive.map(|d| d.naive_local().to_string()),
+            some(eq("2025-01-22 00:00:00"))
+        );
+
+        expect_that!(parse_date("Yesterday"), none());
+        expect_that!(parse_date("<2025-13-01 Wed>"), none());
+    }
+
     #[gtest]
     fn some_code() {
         let contents = indoc! {r###"
@@ -338,16 +680,198 @@ mod tests {
         let tags = org_tags(&doc, contents);
 
         let rel_path = Path::new("simple_doc.org");
-        let html = to_html(doc, &tags, rel_path);
+        let html = to_html(&doc, &tags, rel_path, &Site::default());
 
         expect_that!(
             html,
-            ok(eq(indoc! {r###"
-              <h1>TITLE</h1><section></section><section id="heading" class="s1"><h1><a href="#heading">Heading</a></h1><section><p>Hi
-              </p><pre><code class="language-rust">pub fn main() {
-                println!(&quot;Hi&quot;);
-              }
-              </code></pre></section></section><script src="https://cdn.jsdelivr.net/npm/@arborium/arborium@1/dist/arborium.iife.js" data-theme="ayu-dark"></script>"###})),
+            ok(all![
+                starts_with(indoc! {r###"
+                  <h1>TITLE</h1><section></section><section id="heading" class="s1"><h1><a href="#heading">Heading</a></h1><section><p>Hi
+                  </p><pre class="hl-code"><code class="language-rust"><span class="hl-source hl-rust">"###}),
+                contains_substring(r#"<span class="hl-support hl-macro hl-rust">println!</span>"#),
+                contains_substring("&quot;</span>Hi<span"),
+                ends_with("</code></pre></section></section>"),
+                not(contains_substring("<script")),
+            ]),
+        );
+    }
+
+    #[gtest]
+    fn source_block_exports() {
+        let contents = indoc! {r###"
+          #+begin_src sh :exports code
+          echo code
+          #+end_src
+
+          #+RESULTS:
+          : code result
+
+          #+begin_src sh :exports results
+          echo results
+          #+end_src
+
+          #+RESULTS:
+          : results result
+
+          #+begin_src sh :exports both
+          echo both
+          #+end_src
+
+          #+RESULTS:
+          : both result
+
+          #+begin_src sh :exports none
+          echo none
+          #+end_src
+
+          #+RESULTS:
+          : none result
+        "###};
+        let doc = Org::parse(contents);
+
+        let html = to_html(&doc, &[], Path::new("exports.org"), &Site::default());
+        let code = |text| {
+            format!(
+                r#"<span class="hl-meta hl-function-call hl-arguments hl-shell"> {text}</span>"#
+            )
+        };
+        let result = |text| format!("<samp class=\"org_result\">{text} result\n</samp>");
+        expect_that!(
+            html,
+            ok(all![
+                starts_with(r#"<section><pre class="hl-code"><code class="language-sh">"#),
+                contains_substring(code("code")),
+                not(contains_substring(result("code"))),
+                not(contains_substring(code("results"))),
+                contains_substring(result("results")),
+                contains_substring(code("both")),
+                contains_substring(result("both")),
+                not(contains_substring(code("none"))),
+                not(contains_substring(result("none"))),
+            ])
+        );
+    }
+
+    #[gtest]
+    fn internal_links() {
+        let doc = Org::parse(indoc! {r###"
+          See [[#setup]], [[*Setup][the setup]] and [[*Missing]].
+          * Setup
+          * Setup
+          :PROPERTIES:
+          :CUSTOM_ID: setup
+          :END:
+        "###});
+
+        let html = to_html(&doc, &[], Path::new("links.org"), &Site::default());
+        expect_that!(
+            html,
+            ok(all![
+                contains_substring(
+                    r##"See <a href="#setup">Setup</a>, <a href="#setup-2">the setup</a>"##
+                ),
+                contains_substring(r#"<section id="setup-2" class="s1">"#),
+                contains_substring(r#"<section id="setup" class="s1">"#),
+            ])
+        );
+    }
+
+    #[gtest]
+    fn id_links() {
+        let (mut site, _) = crate::site::tests::site(&[
+            (
+                "b/post.org",
+                ":PROPERTIES:\n:ID: post-id\n:END:\n#+title: A /post/\n* Setup\n:PROPERTIES:\n:ID: 1b2c\n:END:\n",
+            ),
+            (
+                "b/draft.org",
+                ":PROPERTIES:\n:ID: draft-id\n:Draft: t\n:END:\n#+title: Draft\n",
+            ),
+        ]);
+        // Indexed, but not published
+        let private = Org::parse(":PROPERTIES:\n:ID: private-id\n:END:\n#+title: Secret plans\n");
+        site.index(&private, Path::new("private.org"));
+
+        let doc = Org::parse(indoc! {r###"
+          [[id:post-id]], [[id:1b2c][the setup]], [[id:draft-id]], [[id:private-id]]
+          and [[id:missing]].
+        "###});
+        let html = to_html(&doc, &[], Path::new("notes.org"), &site);
+        expect_that!(
+            html,
+            ok(all![
+                contains_substring(r#"href="/b/post" />"#),
+                contains_substring("A <i>post</i></a>,"),
+                contains_substring(r#"hx-swap="innerHTML show:[id='1b2c']:top""#),
+                contains_substring(r#"href="/b/post/#1b2c" />"#),
+                contains_substring("the setup</a>"),
+                contains_substring(r#"<a class="broken-link">id:draft-id</a>"#),
+                contains_substring(r#"<a class="broken-link">id:private-id</a>"#),
+                contains_substring(r#"<a class="broken-link">id:missing</a>"#),
+                not(contains_substring("Draft")),
+                not(contains_substring("Secret")),
+            ])
+        );
+
+        let doc = Org::parse("See [[id:1b2c]].\n");
+        let html = to_html(&doc, &[], Path::new("b/post.org"), &site);
+        expect_that!(
+            html,
+            ok(contains_substring(r##"<a href="#1b2c">Setup</a>"##))
+        );
+    }
+
+    #[gtest]
+    fn tables() {
+        let doc = Org::parse(indoc! {r###"
+          #+caption: Build /times/
+          | Crate | Seconds | Notes |
+          |-------+---------+-------|
+          |       |         | <c>   |
+          | orgize | 1.5    | /fast/ |
+          | image  | 12     | slow  |
+          |-------+---------+-------|
+          | Total  | 13.5   |       |
+        "###});
+
+        let html = to_html(&doc, &[], Path::new("tables.org"), &Site::default());
+        expect_that!(
+            html,
+            ok(eq(concat!(
+                r#"<section><div class="table-wrapper"><table><caption>Build <i>times</i></caption>"#,
+                r#"<thead><tr><th scope="col">Crate</th><th scope="col" class="align-r">Seconds</th><th scope="col" class="align-c">Notes</th></tr></thead>"#,
+                r#"<tbody><tr><td>orgize</td><td class="align-r">1.5</td><td class="align-c"><i>fast</i></td></tr>"#,
+                r#"<tr><td>image</td><td class="align-r">12</td><td class="align-c">slow</td></tr></tbody>"#,
+                r#"<tbody><tr><td>Total</td><td class="align-r">13.5</td><td class="align-c"></td></tr></tbody>"#,
+                "</table></div></section>"
+            )))
+        );
+    }
+
+    #[gtest]
+    fn math() {
+        let doc = Org::parse(indoc! {r###"
+          Energy is $E$ see \eqref{eq:energy} or [[eq:energy][the equation]].
+
+          \begin{equation}
+          E = mc^2 \label{eq:energy}
+          \end{equation}
+        "###});
+
+        let html = to_html(&doc, &[], Path::new("math.org"), &Site::default());
+        expect_that!(
+            html,
+            ok(all![
+                contains_substring(
+                    r#"Energy is <math display="inline" xmlns="http://www.w3.org/1998/Math/MathML"><mi>E</mi></math> see"#
+                ),
+                contains_substring(
+                    r##"see <a href="#eq:energy" class="equation-ref">(1)</a> or <a href="#eq:energy" class="equation-ref">the equation</a>"##
+                ),
+                contains_substring(r#"<div class="equation" id="eq:energy">"#),
+                not(contains_substring("$")),
+                not(contains_substring("\\begin")),
+            ])
         );
     }
 }
@@ -0,0 +1,223 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::links::Target;
+use crate::site::{Page, Site};
+
+/// Pages to publish out of a bigger notes directory, following the links of
+/// some entry points.
+///
+/// Only pages with a `publish` property or one of the publish tags are
+/// followed, links to other pages are cut and reported. Entry points are
+/// published either way.
+#[derive(Debug, Default)]
+pub struct Subgraph {
+    /// `.org` files, relative to the site directory
+    entries: Vec<PathBuf>,
+    /// Tags making every page with them an entry point
+    entry_tags: Vec<String>,
+    /// Tags allowing pages to be published
+    publish_tags: Vec<String>,
+}
+
+impl Subgraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(mut self, entries: &[PathBuf]) -> Self {
+        self.entries = entries.to_vec();
+        self
+    }
+
+    pub fn entry_tags(mut self, tags: &[String]) -> Self {
+        self.entry_tags = tags.to_vec();
+        self
+    }
+
+    pub fn publish_tags(mut self, tags: &[String]) -> Self {
+        self.publish_tags = tags.to_vec();
+        self
+    }
+
+    /// Whether there's any entry point, otherwise every page is published
+    pub fn is_enabled(&self) -> bool {
+        !self.entries.is_empty() || !self.entry_tags.is_empty()
+    }
+
+    /// Whether `page` can be published when linked
+    fn allows(&self, page: &Page) -> bool {
+        page.publish || page.tags.iter().any(|t| self.publish_tags.contains(t))
+    }
+
+    /// Paths of the pages reached from the entry points, through allowed pages.
+    pub fn select(&self, site: &Site) -> HashSet<PathBuf> {
+        let mut selected: HashSet<PathBuf> = HashSet::new();
+        let mut pending: VecDeque<PathBuf> = VecDeque::new();
+
+        for entry in &self.entries {
+            if site.get(entry).is_none() {
+                log::warn!("Entry point '{}' isn't a page", entry.display());
+                continue;
+            }
+            pending.push_back(entry.clone());
+        }
+        for page in site.pages() {
+            if page.tags.iter().any(|t| self.entry_tags.contains(t)) {
+                pending.push_back(page.rel_path.clone());
+            }
+        }
+        for entry in &pending {
+            selected.insert(entry.clone());
+        }
+
+        while let Some(rel_path) = pending.pop_front() {
+            for link in site.links(&rel_path) {
+                let Target::Page { page, .. } = link.target(site) else {
+                    continue;
+                };
+                if selected.contains(&page.rel_path) {
+                    continue;
+                }
+                if !self.allows(page) {
+                    log::warn!(
+                        "{}:{}: Cutting link to private '{}'",
+                        rel_path.display(),
+                        link.line,
+                        page.rel_path.display()
+                    );
+                    continue;
+                }
+                selected.insert(page.rel_path.clone());
+                pending.push_back(page.rel_path.clone());
+            }
+        }
+        selected
+    }
+}
+
+/// Non-`.org` files published along the pages selected by a [`Subgraph`].
+///
+/// Files linked from selected pages are published, and files only linked from
+/// cut ones aren't. Files no page links to, like stylesheets, are published.
+#[derive(Debug, Default)]
+pub struct Files {
+    /// Linked from selected pages
+    linked: HashSet<PathBuf>,
+    /// Linked from cut pages
+    cut_links: HashSet<PathBuf>,
+}
+
+impl Files {
+    /// `org_files` are the `.org` files of the site, selected or not.
+    pub fn new(site: &Site, org_files: &[PathBuf], selected: &HashSet<PathBuf>) -> Self {
+        let mut files = Self::default();
+        for rel_path in org_files {
+            let links = if selected.contains(rel_path) {
+                &mut files.linked
+            } else {
+                &mut files.cut_links
+            };
+            for link in site.links(rel_path) {
+                if let Target::Asset(path) = link.target(site) {
+                    links.insert(PathBuf::from(path.trim_start_matches('/')));
+                }
+            }
+        }
+        files
+    }
+
+    /// Whether the file at `rel_path`, relative to the site directory, is published
+    pub fn contains(&self, rel_path: &Path) -> bool {
+        self.linked.contains(rel_path) || !self.cut_links.contains(rel_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::site;
+    use googletest::prelude::*;
+
+    #[gtest]
+    fn follows_allowed_links() {
+        let (mut site, docs) = site::tests::site(&[
+            (
+                "index.org",
+                "#+filetags: :Home:\n[[./public.org]] [[id:tagged]] [[./shared.png]]\n",
+            ),
+            (
+                "public.org",
+                ":PROPERTIES:\n:publish: t\n:END:\n[[./private.org]] [[./index.org]]\n",
+            ),
+            (
+                "tagged.org",
+                ":PROPERTIES:\n:ID: tagged\n:END:\n#+filetags: :Blog:\n",
+            ),
+            (
+                "private.org",
+                ":PROPERTIES:\n:ID: private\n:END:\n[[./unreached.org]] [[./shared.png]] [[./private.png]]\n",
+            ),
+            ("unreached.org", ":PROPERTIES:\n:publish: t\n:END:\n"),
+            ("off.org", ":PROPERTIES:\n:publish: nil\n:END:\n"),
+            ("notes/secret.org", "[[../index.org]] [[./figure.png]]\n"),
+        ]);
+        expect_that!(site.get(Path::new("off.org")).unwrap().publish, eq(false));
+
+        let subgraph = Subgraph::new()
+            .entry_tags(&[String::from("Home")])
+            .publish_tags(&[String::from("Blog")]);
+        let selected = subgraph.select(&site);
+        let mut sorted: Vec<PathBuf> = selected.iter().cloned().collect();
+        sorted.sort();
+        expect_that!(
+            sorted,
+            elements_are![
+                eq(Path::new("index.org")),
+                eq(Path::new("public.org")),
+                eq(Path::new("tagged.org")),
+            ]
+        );
+        expect_that!(Subgraph::new().is_enabled(), eq(false));
+
+        let org_files: Vec<PathBuf> = docs.into_iter().map(|(p, _)| p).collect();
+        let files = Files::new(&site, &org_files, &selected);
+        for (file, published) in [
+            ("shared.png", true),
+            ("private.png", false),
+            ("notes/figure.png", false),
+            ("index.html", true),
+            ("s/_.css", true),
+        ] {
+            expect_that!(files.contains(Path::new(file)), eq(published), "{file}");
+        }
+
+        // Away from the root, files no page links to are still published
+        let entry = Subgraph::new().entries(&[PathBuf::from("notes/secret.org")]);
+        let entry_selected = entry.select(&site);
+        expect_that!(
+            entry_selected,
+            eq(&HashSet::from([PathBuf::from("notes/secret.org")]))
+        );
+        let files = Files::new(&site, &org_files, &entry_selected);
+        for (file, published) in [
+            ("notes/figure.png", true),
+            ("shared.png", false),
+            ("index.html", true),
+            ("s/_.css", true),
+            ("l/htmx.min.js", true),
+        ] {
+            expect_that!(files.contains(Path::new(file)), eq(published), "{file}");
+        }
+
+        site.retain(|p| selected.contains(&p.rel_path));
+        expect_that!(site.id("tagged"), some(anything()));
+        expect_that!(site.id("private"), none());
+        expect_that!(site.anchors(Path::new("private.org")), none());
+        expect_that!(site.links(Path::new("private.org")), is_empty());
+        expect_that!(
+            site.links(Path::new("public.org"))[0].target(&site),
+            eq(&Target::Broken)
+        );
+    }
+}
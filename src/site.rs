@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use orgize::Org;
+use orgize::ast::{Headline, Link};
+use orgize::rowan::ast::AstNode;
+
+use crate::anchors::Anchors;
+use crate::backlinks::Backlink;
+use crate::links::{self, PageLink, Target};
+use crate::page;
+
+/// Metadata of a published page, gathered before rendering anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    /// Path of the `.org` file, relative to the site directory
+    pub rel_path: PathBuf,
+    /// `#+title:`, or the file stem when missing
+    pub title: String,
+    /// `description` property
+    pub description: Option<String>,
+    /// `publication_date` property, or `created_time` when missing
+    pub date: Option<chrono::DateTime<chrono::FixedOffset>>,
+    /// Whether `publication_date` is set, ie it's a post rather than a standalone page
+    pub post: bool,
+    /// `series` property, grouping multi-part posts
+    pub series: Option<String>,
+    pub tags: Vec<String>,
+    /// `Draft` property
+    pub draft: bool,
+    /// `publish` property, unless `nil`, `no` or `false`
+    pub publish: bool,
+}
+
+impl Page {
+    pub fn new(doc: &Org, tags: &[String], rel_path: &Path) -> Self {
+        let properties = doc.document().properties();
+        let publication_date = page::org_date(doc, "publication_date", rel_path);
+
+        Self {
+            rel_path: rel_path.to_path_buf(),
+            title: title(doc, rel_path),
+            description: properties
+                .as_ref()
+                .and_then(|p| p.get("description"))
+                .map(|d| d.to_string()),
+            post: publication_date.is_some(),
+            date: publication_date.or_else(|| page::org_date(doc, "created_time", rel_path)),
+            series: properties
+                .as_ref()
+                .and_then(|p| p.get("series"))
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+            tags: tags.to_vec(),
+            draft: properties
+                .as_ref()
+                .is_some_and(|p| p.get("Draft").is_some()),
+            publish: properties.and_then(|p| p.get("publish")).is_some_and(|p| {
+                !["nil", "no", "false"].contains(&p.trim().to_lowercase().as_str())
+            }),
+        }
+    }
+
+    /// Site path of the page, `/path/to/page`
+    pub fn path(&self) -> String {
+        let mut path = self.rel_path.clone();
+        path.set_extension("");
+        format!("/{}", path.display())
+    }
+}
+
+/// `#+title:`, or the file stem when missing
+fn title(doc: &Org, rel_path: &Path) -> String {
+    doc.title().unwrap_or_else(|| {
+        rel_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    })
+}
+
+/// What an org `ID` property identifies, the target of `id:` links.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdTarget {
+    /// Path of the `.org` file, relative to the site directory
+    pub rel_path: PathBuf,
+    /// Anchor of the headline, `None` for the whole file
+    pub anchor: Option<String>,
+    /// Title of the headline or page
+    pub title: String,
+}
+
+/// Every page on the site.
+///
+/// Built on a first pass over the site directory so pages can refer to each other.
+#[derive(Debug, Default)]
+pub struct Site {
+    pages: Vec<Page>,
+    /// `ID` properties of every org file, published or not
+    ids: HashMap<String, IdTarget>,
+    /// Section anchors of every org file, by its path
+    anchors: HashMap<PathBuf, Anchors>,
+    /// Links of every page, in order, by its path
+    links: HashMap<PathBuf, Vec<PageLink>>,
+    /// Pages linking to each page, by its path
+    backlinks: HashMap<PathBuf, Vec<Backlink>>,
+    /// Directory the site is built from
+    dir: PathBuf,
+}
+
+impl Site {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the files pages link to, like images, from `dir`.
+    pub fn dir(mut self, dir: &Path) -> Self {
+        self.dir = dir.to_path_buf();
+        self
+    }
+
+    /// Path of a site file on disk
+    pub fn source_path(&self, rel_path: &Path) -> PathBuf {
+        self.dir.join(rel_path)
+    }
+
+    pub fn push(&mut self, page: Page) {
+        self.pages.push(page);
+    }
+
+    /// Keeps only the pages `keep` returns `true` for, forgetting the `ID`s,
+    /// anchors and links of the rest.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Page) -> bool) {
+        let (kept, dropped) = self.pages.drain(..).partition(|p| keep(p));
+        self.pages = kept;
+        for page in dropped {
+            self.ids
+                .retain(|_, target| target.rel_path != page.rel_path);
+            self.anchors.remove(&page.rel_path);
+            self.links.remove(&page.rel_path);
+        }
+    }
+
+    /// Indexes the file and headline `ID` properties of `doc`, and its
+    /// section anchors, so other pages can link to them.
+    ///
+    /// Files that aren't published are indexed too, so links to them can be
+    /// told apart from links to unknown IDs.
+    pub fn index(&mut self, doc: &Org, rel_path: &Path) {
+        let mut insert = |id: &str, anchor: Option<String>, title: String| {
+            let id = id.trim();
+            if id.is_empty() {
+                return;
+            }
+            let target = IdTarget {
+                rel_path: rel_path.to_path_buf(),
+                anchor,
+                title,
+            };
+            if let Some(previous) = self.ids.insert(id.to_string(), target) {
+                log::warn!(
+                    "{}: ID '{id}' is also used on '{}'",
+                    rel_path.display(),
+                    previous.rel_path.display()
+                );
+            }
+        };
+
+        if let Some(id) = doc.document().properties().and_then(|p| p.get("ID")) {
+            insert(&id, None, title(doc, rel_path));
+        }
+
+        let anchors = Anchors::new(doc);
+        for headline in doc
+            .document()
+            .syntax()
+            .descendants()
+            .filter_map(Headline::cast)
+        {
+            if let Some(id) = headline.properties().and_then(|p| p.get("ID")) {
+                insert(
+                    &id,
+                    anchors.get(&headline).map(String::from),
+                    page::headline_title(&headline),
+                );
+            }
+        }
+        self.anchors.insert(rel_path.to_path_buf(), anchors);
+    }
+
+    /// Resolves the links of `doc`, the page at `rel_path`, so they're only
+    /// resolved once. Every page must be pushed and indexed already.
+    pub fn resolve_links(&mut self, doc: &Org, rel_path: &Path) {
+        let source = doc.document().syntax().to_string();
+        let links = doc
+            .document()
+            .syntax()
+            .descendants()
+            .filter_map(Link::cast)
+            .map(|link| PageLink::new(&link, &source, self, rel_path))
+            .collect();
+        self.links.insert(rel_path.to_path_buf(), links);
+    }
+
+    /// Resolved links of the page at `rel_path`, in order
+    pub fn links(&self, rel_path: &Path) -> &[PageLink] {
+        self.links.get(rel_path).map_or(&[], |l| l.as_slice())
+    }
+
+    /// Target of `link`, on the page at `rel_path`. Links that weren't
+    /// resolved are broken.
+    pub fn target(&self, rel_path: &Path, link: &Link) -> Target<'_> {
+        let links = self.links(rel_path);
+        match links.binary_search_by_key(&links::offset(link), |l| l.offset) {
+            Ok(index) => links[index].target(self),
+            Err(_) => Target::Broken,
+        }
+    }
+
+    /// Records the links from the page at `rel_path`, as `(target, context)`
+    /// pairs from [`crate::backlinks::links_from`].
+    pub fn push_backlinks(&mut self, rel_path: &Path, links: Vec<(PathBuf, String)>) {
+        for (target, context) in links {
+            let backlinks = self.backlinks.entry(target).or_default();
+            let index = match backlinks.iter().position(|b| b.rel_path == rel_path) {
+                Some(index) => index,
+                None => {
+                    backlinks.push(Backlink {
+                        rel_path: rel_path.to_path_buf(),
+                        contexts: vec![],
+                    });
+                    backlinks.len() - 1
+                }
+            };
+            let contexts = &mut backlinks[index].contexts;
+            if !contexts.contains(&context) {
+                contexts.push(context);
+            }
+        }
+    }
+
+    /// Pages linking to `rel_path`
+    pub fn backlinks(&self, rel_path: &Path) -> &[Backlink] {
+        self.backlinks.get(rel_path).map_or(&[], |b| b.as_slice())
+    }
+
+    /// Section anchors of the org file at `rel_path`
+    pub fn anchors(&self, rel_path: &Path) -> Option<&Anchors> {
+        self.anchors.get(rel_path)
+    }
+
+    /// Target of an `id:` link
+    pub fn id(&self, id: &str) -> Option<&IdTarget> {
+        self.ids.get(id.trim())
+    }
+
+    /// Pages that can be listed, ie not drafts.
+    pub fn pages(&self) -> impl Iterator<Item = &Page> {
+        self.pages.iter().filter(|p| !p.draft)
+    }
+
+    pub fn get(&self, rel_path: &Path) -> Option<&Page> {
+        self.pages.iter().find(|p| p.rel_path == rel_path)
+    }
+
+    /// Posts on the same directory as `rel_path`, oldest first.
+    pub fn posts_beside(&self, rel_path: &Path) -> Vec<&Page> {
+        let dir = rel_path.parent();
+        let mut posts: Vec<&Page> = self
+            .pages()
+            .filter(|p| p.post && p.rel_path.parent() == dir)
+            .collect();
+        posts.sort_by(|a, b| (a.date, &a.rel_path).cmp(&(b.date, &b.rel_path)));
+        posts
+    }
+
+    /// Up to `max` other pages sharing tags with `rel_path`.
+    ///
+    /// Pages sharing more tags go first, then the newest ones.
+    pub fn related(&self, rel_path: &Path, max: usize) -> Vec<&Page> {
+        let Some(current) = self.get(rel_path) else {
+            return vec![];
+        };
+
+        let mut related: Vec<(usize, &Page)> = self
+            .pages()
+            .filter(|p| p.rel_path != rel_path)
+            .map(|p| {
+                let shared = p.tags.iter().filter(|t| current.tags.contains(t)).count();
+                (shared, p)
+            })
+            .filter(|(shared, _)| *shared > 0)
+            .collect();
+        related.sort_by(|(a_shared, a), (b_shared, b)| {
+            (b_shared, b.date, &a.rel_path).cmp(&(a_shared, a.date, &b.rel_path))
+        });
+        related.into_iter().take(max).map(|(_, p)| p).collect()
+    }
+
+    /// Parts of a series, oldest first.
+    pub fn series(&self, name: &str) -> Vec<&Page> {
+        let mut parts: Vec<&Page> = self
+            .pages()
+            .filter(|p| p.series.as_deref() == Some(name))
+            .collect();
+        parts.sort_by(|a, b| (a.date, &a.rel_path).cmp(&(b.date, &b.rel_path)));
+        parts
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Site of the `(path, contents)` org files, all indexed and published
+    /// with their links resolved, and their documents.
+    pub fn site(files: &[(&str, &str)]) -> (Site, Vec<(PathBuf, Org)>) {
+        let mut site = Site::default();
+        let mut docs = vec![];
+        for (rel_path, contents) in files {
+            let rel_path = PathBuf::from(rel_path);
+            let doc = Org::parse(contents);
+            site.index(&doc, &rel_path);
+            site.push(Page::new(&doc, &page::org_tags(&doc, contents), &rel_path));
+            docs.push((rel_path, doc));
+        }
+        for (rel_path, doc) in &docs {
+            site.resolve_links(doc, rel_path);
+        }
+        (site, docs)
+    }
+}
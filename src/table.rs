@@ -0,0 +1,362 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::sync::LazyLock;
+
+use orgize::ast::{OrgTable, OrgTableCell, OrgTableRow};
+use orgize::export::HtmlEscape;
+use orgize::rowan::Direction;
+use orgize::rowan::ast::AstNode;
+use orgize::{SyntaxElement, SyntaxKind, TextRange};
+use regex::Regex;
+
+use crate::page;
+
+/// `<l>`, `<c10>`, `<r>` or `<10>` cookie, setting the alignment and width of a column
+static COOKIE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^<([lcr])?[0-9]*>$").expect("Valid regex"));
+
+/// Numbers, like `org-table-number-regexp`
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:[<>]?[-+^.0-9]*[0-9][-+^.0-9eEdDx()%:]*|[<>]?[-+]?0[xX][[:xdigit:].]+|nan|[-+u]?inf)$")
+        .expect("Valid regex")
+});
+
+/// Fraction of numbers a column needs to be right aligned, like
+/// `org-table-number-fraction`
+const NUMBER_FRACTION: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+impl Alignment {
+    fn class(self) -> &'static str {
+        match self {
+            Self::Left => "align-l",
+            Self::Center => "align-c",
+            Self::Right => "align-r",
+        }
+    }
+}
+
+/// Rendering state of an org table, as its rows and cells are traversed.
+///
+/// The first group of rows, if followed by a rule, is the `<thead>`. Rows of
+/// `<l>`, `<c>` and `<r>` cookies align their columns, and a row starting
+/// with `/` groups them with `<` and `>`. Those rows aren't rendered. Columns
+/// mostly made of numbers are right aligned.
+#[derive(Debug)]
+pub struct Table {
+    alignments: Vec<Option<Alignment>>,
+    /// Whether each column starts or ends a group, if grouped
+    groups: Vec<(bool, bool)>,
+    /// Cookie and column group rows, by their position
+    special_rows: HashSet<TextRange>,
+    has_header: bool,
+    /// Cells of the widest row
+    columns: usize,
+    /// `thead` or `tbody` being rendered
+    section: Option<&'static str>,
+    column: usize,
+}
+
+impl Table {
+    /// Opens the table, captioned by its `#+CAPTION:` and identified by its
+    /// `#+NAME:`.
+    pub fn new(table: &OrgTable) -> (Self, String) {
+        let mut special_rows = HashSet::new();
+        let mut cookies: Vec<Option<Alignment>> = vec![];
+        let mut groups = vec![];
+        // Non-empty body cells of each column, and how many are numbers
+        let mut numbers: Vec<(usize, usize)> = vec![];
+        let mut header_numbers: Vec<(usize, usize)> = vec![];
+        // Groups of rows, split by rules
+        let mut row_groups = vec![0];
+        let mut columns = 0;
+
+        for row in table.syntax().children().filter_map(OrgTableRow::cast) {
+            if row.is_rule() {
+                if row_groups.last() != Some(&0) {
+                    row_groups.push(0);
+                }
+                continue;
+            }
+            let cells = row_cells(&row);
+
+            if cells.first().is_some_and(|c| c == "/")
+                && cells[1..]
+                    .iter()
+                    .all(|c| ["", "<", ">", "<>"].contains(&c.as_str()))
+            {
+                special_rows.insert(row.syntax().text_range());
+                groups = column_groups(&cells);
+                continue;
+            }
+            if cells.iter().any(|c| !c.is_empty())
+                && cells.iter().all(|c| c.is_empty() || COOKIE.is_match(c))
+            {
+                special_rows.insert(row.syntax().text_range());
+                cookies.resize(cookies.len().max(cells.len()), None);
+                for (alignment, cell) in cookies.iter_mut().zip(&cells) {
+                    if let Some(captures) = COOKIE.captures(cell) {
+                        *alignment = captures.get(1).map(|a| match a.as_str() {
+                            "l" => Alignment::Left,
+                            "c" => Alignment::Center,
+                            _ => Alignment::Right,
+                        });
+                    }
+                }
+                continue;
+            }
+
+            columns = columns.max(cells.len());
+            *row_groups.last_mut().unwrap() += 1;
+            let counts = if row_groups.len() == 1 {
+                &mut header_numbers
+            } else {
+                &mut numbers
+            };
+            count_numbers(counts, &cells);
+        }
+        let has_header = row_groups.iter().filter(|rows| **rows > 0).count() > 1;
+        // Without a rule after it, the first group is the body
+        if !has_header {
+            numbers.resize(numbers.len().max(header_numbers.len()), (0, 0));
+            for (column, header) in numbers.iter_mut().zip(header_numbers) {
+                column.0 += header.0;
+                column.1 += header.1;
+            }
+        }
+
+        let alignments = (0..columns)
+            .map(|i| {
+                cookies.get(i).copied().flatten().or_else(|| {
+                    numbers
+                        .get(i)
+                        .filter(|(cells, numbers)| {
+                            *cells > 0 && *numbers as f64 >= NUMBER_FRACTION * *cells as f64
+                        })
+                        .map(|_| Alignment::Right)
+                })
+            })
+            .collect();
+
+        let node = table.syntax();
+        let mut html = String::from(r#"<div class="table-wrapper"><table"#);
+        if let Some(name) = page::affiliated_keyword(node, "NAME") {
+            let _ = write!(html, r#" id="{}""#, HtmlEscape(name));
+        }
+        html.push('>');
+        if let Some(caption) = page::affiliated_keyword(node, "CAPTION") {
+            let _ = write!(html, "<caption>{}</caption>", page::title_html(&caption));
+        }
+
+        let table = Self {
+            alignments,
+            groups,
+            special_rows,
+            has_header,
+            columns,
+            section: None,
+            column: 0,
+        };
+        (table, html)
+    }
+
+    /// Whether the cells of `row` are rendered, rules and special rows aren't
+    pub fn has_cells(&self, row: &OrgTableRow) -> bool {
+        row.is_standard() && !self.special_rows.contains(&row.syntax().text_range())
+    }
+
+    /// Opens a row, and the section it starts. Rules close the section.
+    pub fn enter_row(&mut self, row: &OrgTableRow) -> String {
+        if row.is_rule() {
+            return self.close_section();
+        }
+        if !self.has_cells(row) {
+            return String::new();
+        }
+
+        self.column = 0;
+        match self.section {
+            Some(_) => String::from("<tr>"),
+            None => {
+                // Only the first group is the header
+                let section = if self.has_header {
+                    self.has_header = false;
+                    "thead"
+                } else {
+                    "tbody"
+                };
+                self.section = Some(section);
+                format!("<{section}><tr>")
+            }
+        }
+    }
+
+    /// Closes a row, with empty cells up to the table width
+    pub fn leave_row(&mut self) -> String {
+        self.empty_cells(self.columns) + "</tr>"
+    }
+
+    /// Opens a cell, after the empty ones before it
+    pub fn enter_cell(&mut self, cell: &OrgTableCell) -> String {
+        // Empty cells aren't nodes, only the pipes around them
+        let column = cell
+            .syntax()
+            .siblings_with_tokens(Direction::Prev)
+            .filter(|e| e.kind() == SyntaxKind::PIPE)
+            .count()
+            .saturating_sub(1);
+        let mut html = self.empty_cells(column);
+        html.push_str(&self.open_cell());
+        html
+    }
+
+    fn open_cell(&self) -> String {
+        let mut classes = vec![];
+        if let Some(Some(alignment)) = self.alignments.get(self.column) {
+            classes.push(alignment.class());
+        }
+        if let Some((start, end)) = self.groups.get(self.column) {
+            if *start {
+                classes.push("group-start");
+            }
+            if *end {
+                classes.push("group-end");
+            }
+        }
+
+        let mut html = match self.section {
+            Some("thead") => String::from(r#"<th scope="col""#),
+            _ => String::from("<td"),
+        };
+        if !classes.is_empty() {
+            let _ = write!(html, r#" class="{}""#, classes.join(" "));
+        }
+        html.push('>');
+        html
+    }
+
+    /// Closes the table, and the section still open
+    pub fn close(&mut self) -> String {
+        self.close_section() + "</table></div>"
+    }
+
+    pub fn leave_cell(&mut self) -> &'static str {
+        self.column += 1;
+        match self.section {
+            Some("thead") => "</th>",
+            _ => "</td>",
+        }
+    }
+
+    /// Empty cells from the current column to `column`
+    fn empty_cells(&mut self, column: usize) -> String {
+        let mut html = String::new();
+        while self.column < column {
+            html.push_str(&self.open_cell());
+            html.push_str(self.leave_cell());
+        }
+        html
+    }
+
+    fn close_section(&mut self) -> String {
+        match self.section.take() {
+            Some(section) => format!("</{section}>"),
+            None => String::new(),
+        }
+    }
+}
+
+/// Trimmed text of the cells of a row, empty ones included
+fn row_cells(row: &OrgTableRow) -> Vec<String> {
+    let mut cells: Vec<String> = vec![];
+    for element in row.syntax().children_with_tokens() {
+        match element {
+            SyntaxElement::Token(token) if token.kind() == SyntaxKind::PIPE => {
+                cells.push(String::new());
+            }
+            SyntaxElement::Node(node) if node.kind() == SyntaxKind::ORG_TABLE_CELL => {
+                if let Some(cell) = cells.last_mut() {
+                    cell.push_str(node.to_string().trim());
+                }
+            }
+            _ => {}
+        }
+    }
+    // After the closing pipe
+    if cells.last().is_some_and(|c| c.is_empty()) {
+        cells.pop();
+    }
+    cells
+}
+
+/// Adds the non-empty `cells` of a row, and whether they're numbers, to `counts`
+fn count_numbers(counts: &mut Vec<(usize, usize)>, cells: &[String]) {
+    counts.resize(counts.len().max(cells.len()), (0, 0));
+    for (column, cell) in counts.iter_mut().zip(cells) {
+        if !cell.is_empty() {
+            column.0 += 1;
+            column.1 += usize::from(NUMBER.is_match(cell));
+        }
+    }
+}
+
+/// Whether each column starts or ends a group, from the cells of a `/` row.
+///
+/// Like in org-mode, the first column always starts a group, `<` starts one and
+/// `>` ends one.
+fn column_groups(cells: &[String]) -> Vec<(bool, bool)> {
+    let starts: Vec<bool> = cells
+        .iter()
+        .enumerate()
+        .map(|(i, c)| i == 0 || c == "<" || c == "<>")
+        .collect();
+    cells
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let end =
+                c == ">" || c == "<>" || i + 1 == cells.len() || starts.get(i + 1) == Some(&true);
+            (starts[i], end)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    #[gtest]
+    fn groups() {
+        let cells: Vec<String> = ["/", "<", "", ">", "<>"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        expect_that!(
+            column_groups(&cells),
+            eq(&vec![
+                (true, true),
+                (true, false),
+                (false, false),
+                (false, true),
+                (true, true)
+            ])
+        );
+    }
+
+    #[gtest]
+    fn numbers() {
+        for number in ["12", "-3.5", "1.2e3", "50%", "1.5x", "12:30", "inf"] {
+            expect_that!(NUMBER.is_match(number), eq(true), "{number}");
+        }
+        for text in ["", "12 ms", "v1.2a", "abc"] {
+            expect_that!(NUMBER.is_match(text), eq(false), "{text}");
+        }
+    }
+}
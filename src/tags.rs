@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use orgize::export::HtmlEscape;
+
+use crate::page;
+use crate::site;
+
+/// Directory holding the tag pages
+pub const TAGS_DIR: &str = "tags";
+
+/// Site path of a tag page, `/tags/TAG`
+pub fn tag_path(tag: &str) -> String {
+    format!("/{TAGS_DIR}/{tag}")
+}
+
+/// Pages grouped by their tags.
+#[derive(Debug, Default)]
+pub struct Tags {
+    pages: BTreeMap<String, Vec<site::Page>>,
+}
+
+impl Tags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Groups the listable pages of the site by tag.
+    pub fn from_site(site: &site::Site) -> Self {
+        let mut tags = Self::new();
+        for p in site.pages() {
+            tags.push(p);
+        }
+        tags
+    }
+
+    /// Lists a page under each of its tags. Drafts are kept out.
+    pub fn push(&mut self, page: &site::Page) {
+        if page.draft {
+            return;
+        }
+        for t in &page.tags {
+            self.pages.entry(t.clone()).or_default().push(page.clone());
+        }
+    }
+
+    /// Tag names, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.pages.keys().map(|t| t.as_str())
+    }
+
+    /// Renders the `/tags/` fragment, listing every tag with its page count.
+    pub fn overview_html(&self) -> String {
+        let mut html = String::from("<h1>Tags</h1><ul class=\"tags\">");
+        for (tag, pages) in &self.pages {
+            let _ = write!(
+                html,
+                r#"<li><dd-tag>{}{}</a></dd-tag> <span class="count">{}</span></li>"#,
+                page::local_link(HtmlEscape(tag_path(tag))),
+                HtmlEscape(tag),
+                pages.len(),
+            );
+        }
+        html.push_str("</ul>");
+        html
+    }
+
+    /// Renders the `/tags/TAG/` fragment, listing its pages newest first.
+    pub fn tag_html(&self, tag: &str) -> String {
+        let mut pages: Vec<&site::Page> = self.pages.get(tag).into_iter().flatten().collect();
+        pages.sort_by_key(|p| std::cmp::Reverse(p.date));
+
+        let mut html = format!(
+            r#"<hgroup><h1>{0}</h1><p><a href="{1}/feed.rss">RSS</a></p></hgroup><ul class="tagged">"#,
+            HtmlEscape(tag),
+            HtmlEscape(tag_path(tag)),
+        );
+        for p in pages {
+            html.push_str("<li>");
+            if let Some(date) = p.date {
+                let _ = write!(
+                    html,
+                    r#"<time datetime="{}">{}</time>: "#,
+                    date.to_rfc3339(),
+                    date.format("%Y-%m-%d"),
+                );
+            }
+            let _ = write!(
+                html,
+                "{}{}</a>",
+                page::local_link(HtmlEscape(p.path())),
+                page::title_html(&p.title),
+            );
+            if let Some(description) = &p.description {
+                let _ = write!(html, "<p>{}</p>", HtmlEscape(description));
+            }
+            html.push_str("</li>");
+        }
+        html.push_str("</ul>");
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use indoc::indoc;
+    use orgize::Org;
+    use std::path::Path;
+
+    #[gtest]
+    fn tag_pages() {
+        let mut tags = Tags::new();
+
+        let old = Org::parse(indoc! {r###"
+          :PROPERTIES:
+          :created_time: 2024-01-01T00:00:00+00:00
+          :END:
+          #+title: Old ~post~
+        "###});
+        tags.push(&site::Page::new(
+            &old,
+            &[String::from("Rust")],
+            Path::new("b/old.org"),
+        ));
+
+        let new = Org::parse(indoc! {r###"
+          :PROPERTIES:
+          :publication_date: Wed, 22 Jan 2025 15:05 PDT
+          :description: Newer
+          :END:
+          #+title: New
+        "###});
+        tags.push(&site::Page::new(
+            &new,
+            &[String::from("Rust"), String::from("Org")],
+            Path::new("b/new.org"),
+        ));
+
+        let draft = Org::parse(":PROPERTIES:\n:Draft: True\n:END:\n");
+        tags.push(&site::Page::new(
+            &draft,
+            &[String::from("Rust")],
+            Path::new("b/draft.org"),
+        ));
+
+        expect_that!(tags.names().collect::<Vec<_>>(), eq(&vec!["Org", "Rust"]));
+        expect_that!(
+            tags.overview_html(),
+            all![
+                contains_substring(
+                    "href=\"/tags/Org\" />\nOrg</a></dd-tag> <span class=\"count\">1</span>"
+                ),
+                contains_substring(
+                    "href=\"/tags/Rust\" />\nRust</a></dd-tag> <span class=\"count\">2</span>"
+                ),
+            ]
+        );
+
+        let rust = tags.tag_html("Rust");
+        let new_at = rust.find("/b/new/_.html").unwrap();
+        let old_at = rust.find("/b/old/_.html").unwrap();
+        expect_that!(new_at, lt(old_at));
+        expect_that!(
+            rust,
+            all![
+                contains_substring(
+                    r#"<time datetime="2025-01-22T15:05:00-07:00">2025-01-22</time>: "#
+                ),
+                contains_substring("Old <code>post</code></a>"),
+                contains_substring("<p>Newer</p>"),
+                not(contains_substring("draft")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+
+use orgize::Org;
+use orgize::ast::SourceBlock;
+use orgize::rowan::ast::AstNode;
+use regex::Regex;
+
+use crate::code;
+
+/// `<<name>>`, calls like `<<name()>>` aren't supported
+static NOWEB_REF: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<<([^<>\s()]+)>>").expect("Valid regex"));
+
+/// Extensions for `:tangle yes`, languages not listed are used as is
+static LANGUAGE_EXTENSIONS: &[(&str, &str)] = &[
+    ("bash", "sh"),
+    ("elisp", "el"),
+    ("emacs-lisp", "el"),
+    ("haskell", "hs"),
+    ("javascript", "js"),
+    ("perl", "pl"),
+    ("python", "py"),
+    ("ruby", "rb"),
+    ("rust", "rs"),
+    ("shell", "sh"),
+    ("typescript", "ts"),
+];
+
+/// File a block with `:tangle VALUE` is written to, relative to the page directory.
+///
+/// - `no` tangles nothing.
+/// - `yes` uses the page name with the language extension, `b/post.org` tangles
+///   Rust into `post.rs`.
+/// - Anything else is a path, which can't leave the page directory.
+pub fn target(value: &str, language: Option<&str>, file_rel_path: &Path) -> Option<PathBuf> {
+    let target = match value {
+        "no" | "" => return None,
+        "yes" => {
+            let language = language.unwrap_or("txt");
+            let extension = LANGUAGE_EXTENSIONS
+                .iter()
+                .find(|(l, _)| l.eq_ignore_ascii_case(language))
+                .map_or(language, |(_, extension)| extension);
+            file_rel_path
+                .with_extension(extension)
+                .file_name()
+                .map(PathBuf::from)?
+        }
+        path => PathBuf::from(path),
+    };
+
+    if !target
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        log::warn!(
+            "{}: Not tangling into '{}', it must be a path under the page directory",
+            file_rel_path.display(),
+            target.display()
+        );
+        return None;
+    }
+    Some(target)
+}
+
+/// Tangles the source blocks of a page.
+///
+/// Blocks with the same target are concatenated in order, with a blank line in
+/// between, and their `<<ref>>`s expanded when they have `:noweb yes`.
+///
+/// Returns the contents of every target, relative to the page directory.
+pub fn tangle(doc: &Org, file_rel_path: &Path) -> BTreeMap<PathBuf, String> {
+    let blocks: Vec<(SourceBlock, code::Options)> = doc
+        .document()
+        .syntax()
+        .descendants()
+        .filter_map(SourceBlock::cast)
+        .map(|block| {
+            let options = code::Options::new(&block, 0, file_rel_path);
+            (block, options)
+        })
+        .collect();
+
+    let mut refs: HashMap<&str, String> = HashMap::new();
+    for (block, options) in &blocks {
+        if let Some(name) = &options.noweb_ref {
+            refs.entry(name)
+                .or_default()
+                .push_str(&dedent(&block.value()));
+        }
+    }
+
+    let mut noweb = Noweb {
+        refs: &refs,
+        expanding: vec![],
+        expanded: HashMap::new(),
+    };
+    let mut files: BTreeMap<PathBuf, String> = BTreeMap::new();
+    for (block, options) in &blocks {
+        let Some(target) = &options.tangle else {
+            continue;
+        };
+
+        let mut body = dedent(&block.value());
+        if options.noweb {
+            match noweb.expand(&body, file_rel_path) {
+                Ok(expanded) => body = expanded,
+                Err(cycle) => log::error!(
+                    "{}: Noweb references include themselves, {}, not expanding them",
+                    file_rel_path.display(),
+                    cycle
+                        .iter()
+                        .map(|name| format!("<<{name}>>"))
+                        .collect::<Vec<_>>()
+                        .join(" -> ")
+                ),
+            }
+        }
+
+        let contents = files.entry(target.clone()).or_default();
+        if !contents.is_empty() {
+            contents.push('\n');
+        }
+        contents.push_str(&body);
+    }
+    files
+}
+
+/// Expands `<<ref>>`s, remembering what each name expands to.
+struct Noweb<'a> {
+    /// Bodies of the blocks with each name, concatenated
+    refs: &'a HashMap<&'a str, String>,
+    /// Names being expanded, outermost first, to catch cycles
+    expanding: Vec<&'a str>,
+    /// Names already expanded
+    expanded: HashMap<&'a str, String>,
+}
+
+impl<'a> Noweb<'a> {
+    /// Replaces the `<<ref>>`s of `body`, keeping the text before them on
+    /// every line they expand to, and the text after them on the last one.
+    ///
+    /// Fails with the names forming a cycle when a reference includes itself.
+    fn expand(&mut self, body: &str, file_rel_path: &Path) -> Result<String, Vec<&'a str>> {
+        let mut expanded = String::new();
+        for line in body.lines() {
+            // Line being expanded, and where the text left to copy starts
+            let mut current = String::new();
+            let mut copied = 0;
+            for reference in NOWEB_REF.captures_iter(line) {
+                let Some((&name, _)) = self.refs.get_key_value(&reference[1]) else {
+                    log::warn!(
+                        "{}: Unknown noweb reference <<{}>>",
+                        file_rel_path.display(),
+                        &reference[1]
+                    );
+                    continue;
+                };
+                let whole = reference.get(0).unwrap();
+                current.push_str(&line[copied..whole.start()]);
+                copied = whole.end();
+
+                let prefix = current.clone();
+                let included = self.include(name, file_rel_path)?;
+                let mut included_lines = included.lines();
+                current.push_str(included_lines.next().unwrap_or_default());
+                for included_line in included_lines {
+                    expanded.push_str(&current);
+                    expanded.push('\n');
+                    current = format!("{prefix}{included_line}");
+                }
+            }
+            expanded.push_str(&current);
+            expanded.push_str(&line[copied..]);
+            expanded.push('\n');
+        }
+        Ok(expanded)
+    }
+
+    /// Expansion of the blocks named `name`
+    fn include(&mut self, name: &'a str, file_rel_path: &Path) -> Result<String, Vec<&'a str>> {
+        if let Some(expanded) = self.expanded.get(name) {
+            return Ok(expanded.clone());
+        }
+        if let Some(start) = self.expanding.iter().position(|n| *n == name) {
+            let mut cycle = self.expanding[start..].to_vec();
+            cycle.push(name);
+            return Err(cycle);
+        }
+
+        let refs = self.refs;
+        self.expanding.push(name);
+        let expanded = self.expand(&refs[name], file_rel_path);
+        self.expanding.pop();
+
+        let expanded = expanded?;
+        self.expanded.insert(name, expanded.clone());
+        Ok(expanded)
+    }
+}
+
+/// Removes the indentation shared by every non blank line, like org does when
+/// tangling without `org-src-preserve-indentation`.
+fn dedent(code: &str) -> String {
+    let indent = code
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    code.lines()
+        .map(|l| l.get(indent..).unwrap_or_else(|| l.trim_start()))
+        .fold(String::new(), |acc, l| acc + l + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use indoc::indoc;
+
+    #[gtest]
+    fn targets() {
+        let path = Path::new("b/post.org");
+        expect_that!(target("no", Some("rust"), path), none());
+        expect_that!(
+            target("yes", Some("rust"), path),
+            some(eq(Path::new("post.rs")))
+        );
+        expect_that!(
+            target("src/main.rs", Some("rust"), path),
+            some(eq(Path::new("src/main.rs")))
+        );
+        expect_that!(target("../../etc/passwd", None, path), none());
+        expect_that!(target("/etc/passwd", None, path), none());
+    }
+
+    #[gtest]
+    fn tangles_with_noweb() {
+        let doc = Org::parse(indoc! {r###"
+          #+begin_src rust :tangle main.rs :noweb yes
+            fn main() {
+                <<greeting>>
+            }
+          #+end_src
+
+          #+name: greeting
+          #+begin_src rust
+          let name = "World";
+          println!("Hello, {name}!");
+          #+end_src
+
+          #+begin_src rust :tangle main.rs
+          // <<greeting>> isn't expanded without :noweb
+          #+end_src
+
+          #+begin_src sh :tangle run.sh
+          cargo run
+          #+end_src
+
+          #+begin_src sh
+          not tangled
+          #+end_src
+        "###});
+
+        let files = tangle(&doc, Path::new("b/post.org"));
+        expect_that!(
+            files.keys().collect::<Vec<_>>(),
+            eq(&vec![Path::new("main.rs"), Path::new("run.sh")])
+        );
+        expect_that!(
+            files[Path::new("main.rs")],
+            eq(indoc! {r###"
+              fn main() {
+                  let name = "World";
+                  println!("Hello, {name}!");
+              }
+
+              // <<greeting>> isn't expanded without :noweb
+            "###})
+        );
+        expect_that!(files[Path::new("run.sh")], eq("cargo run\n"));
+    }
+
+    #[gtest]
+    fn references_on_one_line() {
+        let doc = Org::parse(indoc! {r###"
+          #+begin_src text :tangle out.txt :noweb yes
+          - <<a>> and <<b>>, <<c>>!
+          #+end_src
+
+          #+name: a
+          #+begin_src text
+          A
+          #+end_src
+
+          #+name: b
+          #+begin_src text
+          B1
+          B2
+          #+end_src
+        "###});
+
+        let files = tangle(&doc, Path::new("refs.org"));
+        expect_that!(
+            files[Path::new("out.txt")],
+            eq("- A and B1\n- A and B2, <<c>>!\n")
+        );
+    }
+
+    #[gtest]
+    fn recursive_references_stop() {
+        let doc = Org::parse(indoc! {r###"
+          #+name: loop
+          #+begin_src text :tangle loop.txt :noweb yes
+          <<loop>>
+          <<loop>>
+          #+end_src
+
+          #+begin_src text :tangle outer.txt :noweb yes
+          <<outer>>
+          #+end_src
+
+          #+name: outer
+          #+begin_src text :noweb yes
+          <<inner>>
+          #+end_src
+
+          #+name: inner
+          #+begin_src text :noweb yes
+          <<outer>>
+          #+end_src
+        "###});
+
+        let files = tangle(&doc, Path::new("loop.org"));
+        expect_that!(files[Path::new("loop.txt")], eq("<<loop>>\n<<loop>>\n"));
+        expect_that!(files[Path::new("outer.txt")], eq("<<outer>>\n"));
+    }
+}
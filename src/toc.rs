@@ -0,0 +1,170 @@
+use orgize::Org;
+use orgize::ast::{Headline, Keyword};
+use orgize::export::HtmlEscape;
+use orgize::rowan::ast::AstNode;
+
+use crate::anchors::Anchors;
+use crate::page;
+
+/// Keyword placing the table of contents, like `#+TOC: headlines 2`
+pub const TOC_KEYWORD: &str = "TOC";
+
+/// Depth of the table of contents at the top of a page, if any.
+///
+/// A `:toc:` property wins over `#+OPTIONS: toc:N`. Both take a depth, `t`
+/// for every level or `nil` for none.
+pub fn depth(doc: &Org) -> Option<usize> {
+    if let Some(toc) = doc.document().properties().and_then(|p| p.get("toc")) {
+        return parse_depth(&toc);
+    }
+
+    doc.document()
+        .keywords()
+        .filter(|k| k.key().eq_ignore_ascii_case("OPTIONS"))
+        .flat_map(|k| {
+            k.value()
+                .split_whitespace()
+                .filter_map(|o| o.strip_prefix("toc:").map(String::from))
+                .collect::<Vec<_>>()
+        })
+        .last()
+        .and_then(|toc| parse_depth(&toc))
+}
+
+/// Whether the page places its table of contents with a `#+TOC: headlines`
+/// keyword. Other lists, like `#+TOC: tables`, keep the one on top.
+pub fn has_keyword(doc: &Org) -> bool {
+    doc.document()
+        .syntax()
+        .descendants()
+        .filter_map(Keyword::cast)
+        .any(|k| k.key().eq_ignore_ascii_case(TOC_KEYWORD) && keyword_depth(&k.value()).is_some())
+}
+
+fn parse_depth(depth: &str) -> Option<usize> {
+    match depth.trim() {
+        "nil" | "0" => None,
+        "t" => Some(usize::MAX),
+        depth => depth.parse().ok(),
+    }
+}
+
+/// Depth asked by a `#+TOC:` keyword value, like `headlines 2`.
+///
+/// `None` for lists we don't generate, like `tables`.
+pub fn keyword_depth(value: &str) -> Option<usize> {
+    let mut words = value.split_whitespace();
+    match words.next() {
+        Some("headlines") => Some(words.next().and_then(parse_depth).unwrap_or(usize::MAX)),
+        _ => None,
+    }
+}
+
+/// Renders the headlines down to `max_depth` as nested lists linking to their sections.
+///
+/// `.`-prefixed layout headlines are left out, their children take their place.
+/// The "Contents" heading is a `depth` one.
+pub fn to_html(doc: &Org, anchors: &Anchors, max_depth: usize, depth: i8) -> String {
+    let items = items(doc.document().headlines(), anchors, 1, max_depth);
+    if items.is_empty() {
+        return String::new();
+    }
+
+    let heading = page::heading(depth);
+    format!(r#"<nav class="toc"><{heading}>Contents</{heading}><ul>{items}</ul></nav>"#)
+}
+
+/// `<li>` for each headline, with their children in a nested list
+fn items(
+    headlines: impl Iterator<Item = Headline>,
+    anchors: &Anchors,
+    level: usize,
+    max_depth: usize,
+) -> String {
+    if level > max_depth {
+        return String::new();
+    }
+
+    let mut html = String::new();
+    for headline in headlines {
+        let title = page::headline_title(&headline);
+        if title.starts_with('.') {
+            html.push_str(&items(headline.headlines(), anchors, level, max_depth));
+            continue;
+        }
+
+        let children = items(headline.headlines(), anchors, level + 1, max_depth);
+        html.push_str(&format!(
+            r##"<li><a href="#{}">{}</a>{}</li>"##,
+            HtmlEscape(anchors.get(&headline).unwrap_or_default()),
+            page::title_html(&title),
+            if children.is_empty() {
+                children
+            } else {
+                format!("<ul>{children}</ul>")
+            },
+        ));
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use indoc::indoc;
+
+    #[gtest]
+    fn depths() {
+        expect_that!(depth(&Org::parse("* A\n")), none());
+        expect_that!(
+            depth(&Org::parse("#+OPTIONS: num:nil toc:2\n")),
+            some(eq(2))
+        );
+        expect_that!(depth(&Org::parse("#+OPTIONS: toc:nil\n")), none());
+        expect_that!(
+            depth(&Org::parse(
+                ":PROPERTIES:\n:toc: t\n:END:\n#+OPTIONS: toc:nil\n"
+            )),
+            some(eq(usize::MAX))
+        );
+
+        expect_that!(keyword_depth("headlines 1"), some(eq(1)));
+        expect_that!(keyword_depth("headlines"), some(eq(usize::MAX)));
+        expect_that!(keyword_depth("tables"), none());
+
+        expect_that!(has_keyword(&Org::parse("#+TOC: headlines 2\n")), eq(true));
+        expect_that!(has_keyword(&Org::parse("#+TOC: tables\n")), eq(false));
+        expect_that!(has_keyword(&Org::parse("#+TOC: listings\n")), eq(false));
+    }
+
+    #[gtest]
+    fn nested_lists() {
+        let doc = Org::parse(indoc! {r###"
+          * Why ~org~?
+          ** Because
+          *** Too deep
+          * .wide
+          ** Showcase
+        "###});
+
+        expect_that!(
+            to_html(&doc, &Anchors::new(&doc), 2, 2),
+            eq(concat!(
+                r##"<nav class="toc"><h2>Contents</h2><ul>"##,
+                r##"<li><a href="#why-org">Why <code>org</code>?</a><ul><li><a href="#because">Because</a></li></ul></li>"##,
+                r##"<li><a href="#showcase">Showcase</a></li>"##,
+                r##"</ul></nav>"##,
+            ))
+        );
+        let quoted = Org::parse("* Quoted\n:PROPERTIES:\n:CUSTOM_ID: a\"<b\n:END:\n");
+        expect_that!(
+            to_html(&quoted, &Anchors::new(&quoted), 2, 2),
+            contains_substring(r##"<a href="#a&quot;&lt;b">Quoted</a>"##)
+        );
+        expect_that!(
+            to_html(&Org::parse("No headlines\n"), &Anchors::default(), 2, 2),
+            eq("")
+        );
+    }
+}